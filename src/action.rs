@@ -1,11 +1,11 @@
+use crate::error::ParseError;
 use std::fmt::{self, Display};
 use std::iter::Peekable;
 
-const UNRECOGNIZED: &str = "unrecognized input. try 'help' or 'quit'";
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Source {
     Waste,
+    Foundation(usize),
     Tableau { column: usize, row: usize },
 }
 
@@ -13,6 +13,7 @@ impl Display for Source {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             Source::Waste => f.write_str("W"),
+            Source::Foundation(idx) => write!(f, "0{}", (b'A' + idx as u8) as char),
             Source::Tableau { column, row } => write!(f, "{}{}",
                 column + 1,
                 (b'A' + row as u8) as char,
@@ -21,7 +22,7 @@ impl Display for Source {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Destination {
     Foundation(usize),
     Tableau(usize),
@@ -36,7 +37,7 @@ impl Display for Destination {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Action {
     Quit,
     Help,
@@ -59,26 +60,28 @@ impl Display for Action {
 }
 
 impl std::str::FromStr for Action {
-    type Err = &'static str;
+    type Err = ParseError;
     fn from_str(s: &str) -> Result<Action, Self::Err> {
         parse_action(s)
     }
 }
 
 fn parse_source(chars: &mut Peekable<impl Iterator<Item=char>>)
-    -> Result<Source, &'static str>
+    -> Result<Source, ParseError>
 {
     let c = match chars.next() {
         Some(c) => c,
-        None => return Err(UNRECOGNIZED),
+        None => return Err(ParseError::Unrecognized),
     };
     match c {
         'W' => return Ok(Source::Waste),
-        '0' => return Err("can't move from the foundation"),
+        '0' => if let Some(idx) = get_int(chars, 'A', 'D') {
+            return Ok(Source::Foundation(idx));
+        }
         '1' | '2' | '3' | '4' | '5' | '6' | '7' => {
             let column = (c as u32 - '1' as u32) as usize;
             if chars.peek().is_none() {
-                return Err("missing a tableau row letter");
+                return Err(ParseError::MissingRow);
             }
             if let Some(row) = get_int(chars, 'A', 'Z') {
                 return Ok(Source::Tableau { column, row });
@@ -86,15 +89,15 @@ fn parse_source(chars: &mut Peekable<impl Iterator<Item=char>>)
         }
         _ => (),
     }
-    Err(UNRECOGNIZED)
+    Err(ParseError::Unrecognized)
 }
 
 fn parse_destination(chars: &mut Peekable<impl Iterator<Item=char>>)
-    -> Result<Destination, &'static str>
+    -> Result<Destination, ParseError>
 {
     let c = match chars.next() {
         Some(c) => c,
-        None => return Err(UNRECOGNIZED),
+        None => return Err(ParseError::Unrecognized),
     };
     match c {
         '0' => if let Some(idx) = get_int(chars, 'A', 'D') {
@@ -103,14 +106,14 @@ fn parse_destination(chars: &mut Peekable<impl Iterator<Item=char>>)
         '1' | '2' | '3' | '4' | '5' | '6' | '7' => {
             let column = (c as u32 - '1' as u32) as usize;
             if chars.peek().is_some() {
-                return Err("extra input after tableau column number");
+                return Err(ParseError::ExtraAfterColumn);
             }
             return Ok(Destination::Tableau(column));
         }
-        'W' => return Err("can't move to the waste"),
+        'W' => return Err(ParseError::ToWaste),
         _ => (),
     }
-    Err(UNRECOGNIZED)
+    Err(ParseError::Unrecognized)
 }
 
 fn get_int(mut chars: impl Iterator<Item = char>, min: char, max: char) -> Option<usize> {
@@ -122,9 +125,9 @@ fn get_int(mut chars: impl Iterator<Item = char>, min: char, max: char) -> Optio
     None
 }
 
-fn parse_action(s: &str) -> Result<Action, &'static str> {
+fn parse_action(s: &str) -> Result<Action, ParseError> {
     match s.to_ascii_uppercase().as_str() {
-        "" => return Err("enter 'quit' to exit, or try 'help'"),
+        "" => return Err(ParseError::Empty),
         "Q" | "QUIT" => return Ok(Action::Quit),
         "HELP" => return Ok(Action::Help),
         "DD" => return Ok(Action::Draw),
@@ -142,8 +145,36 @@ fn parse_action(s: &str) -> Result<Action, &'static str> {
     let dest = parse_destination(&mut chars)?;
 
     if chars.peek().is_some() {
-        return Err("unrecognized extra input after move");
+        return Err(ParseError::ExtraInput);
     }
 
     Ok(Action::Move(source, dest))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(s: &str) -> String {
+        parse_action(s).expect(s).to_string()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for s in &["DD", "QUIT", "W", "W3", "W0A", "3C", "3C0D", "3C7", "0A", "0B4", "0C0D"] {
+            assert_eq!(*s, round_trip(s));
+        }
+        assert_eq!("1A2", round_trip("1a2"));
+    }
+
+    #[test]
+    fn test_parse_foundation() {
+        assert!(matches!(parse_action("0C"),
+            Ok(Action::QuickMove(Source::Foundation(2)))));
+        assert!(matches!(parse_action("0D5"),
+            Ok(Action::Move(Source::Foundation(3), Destination::Tableau(4)))));
+        assert!(parse_action("0E5").is_err());
+        assert!(parse_action("0").is_err());
+        assert_eq!(Err(ParseError::ToWaste), parse_action("1AW"));
+    }
+}
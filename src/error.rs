@@ -0,0 +1,257 @@
+use crate::action::{Destination, Source};
+use crate::game_state::Card;
+use std::fmt::{self, Display};
+use std::io;
+use std::num::ParseIntError;
+
+/// Why a move can't be made.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MoveErrorKind {
+    NoSuchPile,
+    /// Only a king can be moved to an empty tableau column.
+    NotKing,
+    OntoFaceDown,
+    /// Cards on the tableau must alternate in color.
+    SameColor,
+    /// Cards on the tableau must go down in rank one at a time.
+    NotOneLower,
+    /// Only an ace can be moved to an empty foundation pile.
+    NotAce,
+    /// Cards on a foundation pile must all be the same suit.
+    WrongSuit,
+    /// Cards on a foundation pile must go up in rank one at a time.
+    NotOneHigher,
+    NoPassesLeft,
+    StockEmpty,
+    /// Only the bottom card of a tableau column can go to the foundation.
+    NotBottomCard,
+    AlreadyOnFoundation,
+    /// A card on the foundation can only be moved to a tableau column, which must be given.
+    NeedsDestination,
+    NoFoundationFits,
+    WasteEmpty,
+    FoundationEmpty,
+    FaceDown,
+    NoCard,
+}
+
+impl MoveErrorKind {
+    fn message(self) -> &'static str {
+        use MoveErrorKind::*;
+        match self {
+            NoSuchPile => "no such column",
+            NotKing => "only King can go on empty tableau space",
+            OntoFaceDown => "cannot place on face-down card",
+            SameColor => "cards must differ in color",
+            NotOneLower => "card value is not one higher than that being placed",
+            NotAce => "only Ace can go on empty foundation space",
+            WrongSuit => "cards must match in suit",
+            NotOneHigher => "card value is not one lower than that being placed",
+            NoPassesLeft => "no more passes through the stock are allowed",
+            StockEmpty => "the stock and waste are empty",
+            NotBottomCard => "can only pop off the bottom card of a stack",
+            AlreadyOnFoundation => "card is already on the foundation",
+            NeedsDestination => "specify which tableau column to move the card to",
+            NoFoundationFits => "can't put that on any of the foundation stacks",
+            WasteEmpty => "waste is empty",
+            FoundationEmpty => "foundation is empty",
+            FaceDown => "cannot move face-down card",
+            NoCard => "no card there",
+        }
+    }
+}
+
+/// A move that can't be made: why not, and the card, source, and destination involved, where
+/// there are any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveError {
+    pub kind: MoveErrorKind,
+    pub card: Option<Card>,
+    pub source: Option<Source>,
+    pub destination: Option<Destination>,
+}
+
+impl MoveError {
+    pub fn new(kind: MoveErrorKind) -> Self {
+        Self { kind, card: None, source: None, destination: None }
+    }
+}
+
+impl From<MoveErrorKind> for MoveError {
+    fn from(kind: MoveErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.kind.message())
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Why some text isn't a move.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    Unrecognized,
+    MissingRow,
+    ExtraAfterColumn,
+    ToWaste,
+    ExtraInput,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParseError::*;
+        f.write_str(match self {
+            Empty => "enter 'quit' to exit, or try 'help'",
+            Unrecognized => "unrecognized input. try 'help' or 'quit'",
+            MissingRow => "missing a tableau row letter",
+            ExtraAfterColumn => "extra input after tableau column number",
+            ToWaste => "can't move to the waste",
+            ExtraInput => "unrecognized extra input after move",
+        })
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A rule option set to something other than one of its choices.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RuleError {
+    DrawCount,
+    PassLimit,
+    Scoring,
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RuleError::DrawCount => "draw count must be 1 or 3",
+            RuleError::PassLimit => "pass limit must be 1, 3, or unlimited",
+            RuleError::Scoring => "scoring mode must be simple, standard, or vegas",
+        })
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// Anything that can go wrong while playing, or reading the files that go with a game.
+#[derive(Debug)]
+pub enum Error {
+    Move(MoveError),
+    Parse(ParseError),
+    Rule(RuleError),
+    Io(io::Error),
+    Number(ParseIntError),
+    /// A problem on a line of a file: which kind of file (like `log` or `stats`), which line,
+    /// and what the problem is.
+    Line { file: &'static str, line: usize, error: Box<Error> },
+    /// A field of a line in the statistics file which couldn't be read.
+    Field { name: &'static str, error: Box<Error> },
+    MissingField(&'static str),
+    FieldCount { expected: usize, found: usize },
+    /// A game result other than won or lost.
+    BadResult(String),
+    /// A log outcome other than won, lost, or unfinished.
+    BadOutcome,
+    /// A log in a newer format version than this klon can read.
+    LogTooNew(u32),
+    LogWithoutGame,
+    SaveWithoutGame,
+    NoSavedGame,
+    /// Something done in a saved game, counting from 1, which couldn't be done again.
+    Resume { command: usize, error: Box<Error> },
+    NothingToUndo,
+    NothingToRedo,
+    BadCheckpointName,
+    NoSuchCheckpoint(String),
+    CantAutocomplete,
+}
+
+impl Error {
+    /// This error, as found on a line of a file.
+    pub fn at_line(self, file: &'static str, line: usize) -> Self {
+        Error::Line { file, line, error: Box::new(self) }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            Move(e) => e.fmt(f),
+            Parse(e) => e.fmt(f),
+            Rule(e) => e.fmt(f),
+            Io(e) => e.fmt(f),
+            Number(e) => e.fmt(f),
+            Line { file, line, error } => write!(f, "{} line {}: {}", file, line, error),
+            Field { name, error } => write!(f, "bad {}: {}", name, error),
+            MissingField(name) => write!(f, "missing {}", name),
+            FieldCount { expected, found } => {
+                write!(f, "expected {} fields, not {}", expected, found)
+            }
+            BadResult(s) => write!(f, "expected won or lost, not {:?}", s),
+            BadOutcome => f.write_str("outcome must be won, lost, or unfinished"),
+            LogTooNew(version) => write!(f, "log is in format version {}, which is newer than this \
+                klon understands", version),
+            LogWithoutGame => f.write_str("log doesn't say which game it is for"),
+            SaveWithoutGame => f.write_str("saved game is missing its game number"),
+            NoSavedGame => f.write_str("there is no saved game"),
+            Resume { command, error } => write!(f, "can't resume: move {}: {}", command, error),
+            NothingToUndo => f.write_str("no moves to undo"),
+            NothingToRedo => f.write_str("no moves to redo"),
+            BadCheckpointName => f.write_str("checkpoint names must be one word"),
+            NoSuchCheckpoint(name) => write!(f, "no checkpoint named {:?}", name),
+            CantAutocomplete => f.write_str("stock must be empty and all cards face up"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Move(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Rule(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Number(e) => Some(e),
+            Error::Line { error, .. }
+                | Error::Field { error, .. }
+                | Error::Resume { error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+impl From<MoveError> for Error {
+    fn from(e: MoveError) -> Self {
+        Error::Move(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<RuleError> for Error {
+    fn from(e: RuleError) -> Self {
+        Error::Rule(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(e: ParseIntError) -> Self {
+        Error::Number(e)
+    }
+}
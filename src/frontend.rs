@@ -0,0 +1,205 @@
+use crate::action::Action;
+use crate::game_state::{GameState, GameStatus};
+use crate::viewer::Viewer;
+use std::collections::VecDeque;
+
+/// What to do after a game ends.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EndChoice {
+    NewDeal,
+    Replay,
+    Quit,
+}
+
+/// How to play, for frontends to show when asked for help.
+pub const HELP: &str = "\
+    Move cards by typing the position of\n\
+    the card to be moved, followed by the\n\
+    destination. The columns of cards are\n\
+    numbered, and the rows are letters. To\n\
+    place at the bottom of a column, just\n\
+    specify the column number. Flip a\n\
+    face-down card over by just typing its\n\
+    position, without any destination. As\n\
+    a shortcut, moves to the foundation\n\
+    can omit the destination.";
+
+/// How the game is shown to the player, and how their input gets back to it.
+pub trait Frontend {
+    /// Show the current state of the game.
+    fn render(&mut self, game: &GameState);
+
+    /// Ask for the next move or command. Returns None when there is no more input.
+    fn get_input(&mut self) -> Option<String>;
+
+    /// Ask for a line of text.
+    fn prompt(&mut self, prompt: &str) -> Option<String>;
+
+    /// Ask a yes or no question, and wait for the answer.
+    fn confirm(&mut self, question: &str) -> bool;
+
+    /// Tell the player something, like why their move didn't work.
+    fn write(&mut self, text: &str);
+
+    /// Show some lines of text, until the player is done reading them.
+    fn show_text(&mut self, text: &str);
+
+    /// Explain how to play.
+    fn halp(&mut self);
+
+    /// Wait a moment, so that the player can see what happened.
+    fn pause(&mut self, ms: i32);
+
+    /// Show the end of game screen and ask the player what to do next.
+    fn end_screen(&mut self, game: &GameState, status: GameStatus) -> EndChoice;
+
+    /// Set a move to highlight on the board the next time it's rendered.
+    fn set_hint(&mut self, hint: Option<Action>);
+
+    /// Set the Vegas bankroll as of the start of the game, to be shown along with the score.
+    fn set_bankroll(&mut self, bankroll: Option<i32>);
+
+    /// Let the player step through a logged game until they're done with it.
+    fn view(&mut self, viewer: &mut Viewer);
+}
+
+/// Lets the frontend be picked when the program runs.
+impl<F: Frontend + ?Sized> Frontend for Box<F> {
+    fn render(&mut self, game: &GameState) {
+        (**self).render(game)
+    }
+
+    fn get_input(&mut self) -> Option<String> {
+        (**self).get_input()
+    }
+
+    fn prompt(&mut self, prompt: &str) -> Option<String> {
+        (**self).prompt(prompt)
+    }
+
+    fn confirm(&mut self, question: &str) -> bool {
+        (**self).confirm(question)
+    }
+
+    fn write(&mut self, text: &str) {
+        (**self).write(text)
+    }
+
+    fn show_text(&mut self, text: &str) {
+        (**self).show_text(text)
+    }
+
+    fn halp(&mut self) {
+        (**self).halp()
+    }
+
+    fn pause(&mut self, ms: i32) {
+        (**self).pause(ms)
+    }
+
+    fn end_screen(&mut self, game: &GameState, status: GameStatus) -> EndChoice {
+        (**self).end_screen(game, status)
+    }
+
+    fn set_hint(&mut self, hint: Option<Action>) {
+        (**self).set_hint(hint)
+    }
+
+    fn set_bankroll(&mut self, bankroll: Option<i32>) {
+        (**self).set_bankroll(bankroll)
+    }
+
+    fn view(&mut self, viewer: &mut Viewer) {
+        (**self).view(viewer)
+    }
+}
+
+/// A frontend that takes its input from a script, and keeps everything it is asked to show, for
+/// testing.
+///
+/// Each line of the script answers whatever the game asks next: a move or command, a prompt, a
+/// yes or no question (`y` for yes), or what to do at the end of a game (`n`, `r`, or `q`). Once
+/// the script runs out, the input ends, questions are answered no, and games end by quitting.
+#[derive(Debug, Default)]
+pub struct Scripted {
+    script: VecDeque<String>,
+    /// Everything written to the player, in order.
+    pub messages: Vec<String>,
+    /// Everything shown with `show_text`, in order.
+    pub texts: Vec<String>,
+    /// The game as it was last rendered.
+    pub screen: Option<GameState>,
+    pub hint: Option<Action>,
+    pub bankroll: Option<i32>,
+    /// The game as it was at each end of game screen.
+    pub endings: Vec<(GameState, GameStatus)>,
+    /// How many logged games were viewed.
+    pub views: usize,
+}
+
+impl Scripted {
+    pub fn new(script: &[&str]) -> Self {
+        Self {
+            script: script.iter().map(|&line| line.to_owned()).collect(),
+            ..Self::default()
+        }
+    }
+
+    fn next_line(&mut self) -> Option<String> {
+        self.script.pop_front()
+    }
+}
+
+impl Frontend for Scripted {
+    fn render(&mut self, game: &GameState) {
+        self.screen = Some(game.clone());
+    }
+
+    fn get_input(&mut self) -> Option<String> {
+        self.next_line()
+    }
+
+    fn prompt(&mut self, _prompt: &str) -> Option<String> {
+        self.next_line()
+    }
+
+    fn confirm(&mut self, _question: &str) -> bool {
+        self.next_line().is_some_and(|answer| answer.eq_ignore_ascii_case("y"))
+    }
+
+    fn write(&mut self, text: &str) {
+        self.messages.push(text.to_owned());
+    }
+
+    fn show_text(&mut self, text: &str) {
+        self.texts.push(text.to_owned());
+    }
+
+    fn halp(&mut self) {
+        self.texts.push("help".to_owned());
+    }
+
+    fn pause(&mut self, _ms: i32) {}
+
+    fn end_screen(&mut self, game: &GameState, status: GameStatus) -> EndChoice {
+        self.endings.push((game.clone(), status));
+        match self.next_line().as_deref() {
+            Some("n") => EndChoice::NewDeal,
+            Some("r") => EndChoice::Replay,
+            _ => EndChoice::Quit,
+        }
+    }
+
+    fn set_hint(&mut self, hint: Option<Action>) {
+        self.hint = hint;
+    }
+
+    fn set_bankroll(&mut self, bankroll: Option<i32>) {
+        self.bankroll = bankroll;
+    }
+
+    fn view(&mut self, viewer: &mut Viewer) {
+        viewer.jump(viewer.move_count());
+        self.views += 1;
+    }
+}
@@ -0,0 +1,617 @@
+use crate::action::Action;
+use crate::deal;
+use crate::error::Error;
+use crate::frontend::{EndChoice, Frontend};
+use crate::game_state::{GameState, GameStatus, UndoRecord};
+use crate::highscores::{Entry, HighScores};
+use crate::hint;
+use crate::history::History;
+use crate::movelog::{self, LogHeader, Replay};
+use crate::rules::Rules;
+use crate::save::{Command, SavedGame};
+use crate::scoring::ScoringMode;
+use crate::stats::{self, GameResult, Summary};
+use crate::storage;
+use crate::viewer::Viewer;
+use getrandom::getrandom;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// A game being played through a frontend, along with everything that goes with it: undo
+/// history, saving and resuming, statistics, and replaying logs.
+pub struct Game<F> {
+    state: GameState,
+    /// Records for undoing each of the moves made to reach the current position.
+    undo: Vec<UndoRecord>,
+    history: History,
+    journal: Vec<Command>,
+    /// How many moves have been made in the game, counting ones that were later undone.
+    moves_made: usize,
+    ui: F,
+    /// A log file whose moves are being replayed.
+    replay: Option<Replay>,
+    /// Which line of the log the input being handled came from, if it came from one.
+    replay_line: Option<usize>,
+    start_time: Instant,
+    results: Vec<GameResult>,
+    bankroll: Option<i32>,
+    autocomplete_enabled: bool,
+    /// Whether the current game is the one in the saved game file.
+    saved: bool,
+    /// The number of the game that was saved when quitting, if any.
+    suspended: Option<u64>,
+    /// Where to keep saved games, statistics, and high scores, if not the player's data
+    /// directory.
+    data_dir: Option<PathBuf>,
+}
+
+/// How long to show each move made by autocomplete.
+const AUTOCOMPLETE_DELAY_MS: i32 = 100;
+
+/// How much of a name is kept in the high score table.
+const MAX_NAME_LENGTH: usize = 16;
+
+pub fn random_game_number() -> u64 {
+    let mut bytes = [0u8; 8];
+    getrandom(&mut bytes).expect("unable to get random bytes");
+    u64::from_le_bytes(bytes)
+}
+
+impl<F: Frontend> Game<F> {
+    pub fn new(game_number: u64, rules: Rules, ui: F) -> Self {
+        let state = deal(game_number, rules);
+
+        Self {
+            state,
+            undo: vec![],
+            history: History::new(),
+            journal: vec![],
+            moves_made: 0,
+            ui,
+            replay: None,
+            replay_line: None,
+            start_time: Instant::now(),
+            results: vec![],
+            bankroll: None,
+            autocomplete_enabled: false,
+            saved: false,
+            suspended: None,
+            data_dir: None,
+        }
+    }
+
+    /// Throw away the current game and start a new one with the same rules.
+    fn start(&mut self, game_number: u64) {
+        self.deal(game_number, self.state.rules());
+    }
+
+    fn deal(&mut self, game_number: u64, rules: Rules) {
+        self.state = deal(game_number, rules);
+        self.undo.clear();
+        self.history = History::new();
+        self.journal.clear();
+        self.moves_made = 0;
+        self.replay = None;
+        self.start_time = Instant::now();
+        self.saved = false;
+    }
+
+    /// The directory to keep saved games, statistics, and high scores in.
+    fn data_dir(&self) -> io::Result<PathBuf> {
+        match self.data_dir {
+            Some(ref dir) => Ok(dir.clone()),
+            None => storage::data_dir(),
+        }
+    }
+
+    /// Save the current game so it can be resumed later.
+    fn save(&mut self) -> io::Result<()> {
+        SavedGame {
+            game_number: self.state.game_number(),
+            rules: self.state.rules(),
+            elapsed: self.start_time.elapsed(),
+            journal: self.journal.clone(),
+        }.save(&self.data_dir()?)?;
+        self.saved = true;
+        Ok(())
+    }
+
+    /// Replace the current game with a saved one, by doing everything that was done in it again.
+    fn resume(&mut self, saved: SavedGame) -> Result<(), Error> {
+        self.deal(saved.game_number, saved.rules);
+        for (i, command) in saved.journal.into_iter().enumerate() {
+            let result = match command {
+                Command::Play(action) => self.play(action),
+                Command::Undo => self.undo(),
+                Command::Redo => self.redo(),
+                Command::Mark(name) => self.mark(&name),
+                Command::Goto(name) => self.goto(&name),
+            };
+            result.map_err(|e| Error::Resume { command: i + 1, error: Box::new(e) })?;
+        }
+        self.start_time = Instant::now().checked_sub(saved.elapsed).unwrap_or_else(Instant::now);
+        self.saved = true;
+        Ok(())
+    }
+
+    /// If there's a saved game, ask whether to resume it.
+    pub fn offer_resume(&mut self) {
+        let saved = self.data_dir().map_err(Error::from).and_then(|dir| SavedGame::load(&dir));
+        let saved = match saved {
+            Ok(Some(saved)) => saved,
+            Ok(None) => return,
+            Err(e) => {
+                self.ui.write(&e.to_string());
+                return;
+            }
+        };
+        if self.ui.confirm(&format!("resume saved game #{}? (y/n) ", saved.game_number)) {
+            if let Err(e) = self.resume(saved) {
+                self.ui.write(&e.to_string());
+            }
+        }
+    }
+
+    /// Stop playing the current game for now: save it if it's been started, or else just count
+    /// it as played.
+    fn suspend(&mut self) {
+        if self.journal.is_empty() {
+            self.record_result();
+            return;
+        }
+        match self.save() {
+            Ok(()) => self.suspended = Some(self.state.game_number()),
+            Err(e) => {
+                self.ui.write(&format!("unable to save game: {}", e));
+                self.ui.pause(2000);
+                self.record_result();
+            }
+        }
+    }
+
+    /// Record the result of the game, which is over now.
+    fn record_result(&mut self) {
+        if self.saved {
+            // The saved game is over, so it shouldn't be resumed.
+            if let Ok(dir) = self.data_dir() {
+                SavedGame::remove(&dir).ok();
+            }
+            self.saved = false;
+        }
+        // A deal that wasn't played at all doesn't count against the player: it isn't in the
+        // statistics, and doesn't cost anything from the Vegas bankroll.
+        if self.moves_made == 0 {
+            return;
+        }
+        let result = GameResult {
+            game_number: self.state.game_number(),
+            rules: self.state.rules(),
+            score: self.state.score(),
+            moves: self.moves_made,
+            duration: self.start_time.elapsed(),
+            won: self.state.is_won(),
+        };
+        if let Err(e) = self.data_dir().and_then(|dir| stats::record(&dir, &result)) {
+            self.ui.write(&format!("unable to record statistics: {}", e));
+        }
+        self.results.push(result);
+        let vegas = self.state.rules().scoring == ScoringMode::Vegas;
+        if let Some(bankroll) = self.bankroll.as_mut().filter(|_| vegas) {
+            *bankroll += self.state.score();
+            self.ui.set_bankroll(Some(*bankroll));
+        }
+    }
+
+    /// If the game just won has a high score, ask for the player's name and show them where they
+    /// placed.
+    fn record_high_score(&mut self) {
+        let scores = self.data_dir().map_err(Error::from).and_then(|dir| HighScores::load(&dir));
+        let mut scores = match scores {
+            Ok(scores) => scores,
+            Err(e) => {
+                self.ui.write(&format!("unable to load high scores: {}", e));
+                return;
+            }
+        };
+        let rules = self.state.rules();
+        if !scores.qualifies(&rules, self.state.score()) {
+            return;
+        }
+        let name = match self.ui.prompt("high score! your name: ") {
+            Some(name) => name.trim().chars().take(MAX_NAME_LENGTH).collect::<String>(),
+            None => return,
+        };
+        scores.add(rules, Entry {
+            name: if name.is_empty() { "anonymous".to_owned() } else { name },
+            score: self.state.score(),
+            game_number: self.state.game_number(),
+            duration: self.start_time.elapsed(),
+        });
+        if let Err(e) = self.data_dir().and_then(|dir| scores.save(&dir)) {
+            self.ui.write(&format!("unable to save high scores: {}", e));
+            return;
+        }
+        self.ui.show_text(&scores.format_table(&rules));
+        self.ui.render(&self.state);
+    }
+
+    fn get_input_text(&mut self) -> Result<Option<String>, Error> {
+        loop {
+            self.replay_line = None;
+            let input = match self.replay.as_mut() {
+                Some(replay) => {
+                    let line = match replay.next_line()? {
+                        Some(line) => line,
+                        None => {
+                            // EOF
+                            self.replay = None;
+                            continue;
+                        }
+                    };
+                    if line.starts_with('#') {
+                        continue;
+                    }
+                    self.replay_line = Some(replay.line());
+                    Some(line)
+                }
+                None => self.ui.get_input(),
+            };
+
+            if let Some(ref input) = input {
+                let lc = input.trim().to_ascii_lowercase();
+                if lc.starts_with("load ") {
+                    let (replay, header, warnings) = Replay::open(input.trim()[5..].trim())?;
+                    if !self.saved && !self.journal.is_empty() {
+                        self.record_result();
+                    }
+                    self.deal(replay.game_number, header.rules);
+                    self.start_replay(replay, &warnings);
+                    self.ui.render(&self.state);
+                    continue;
+                }
+                if lc.starts_with("view ") {
+                    self.view(input.trim()[5..].trim())?;
+                    self.ui.render(&self.state);
+                    continue;
+                }
+                if lc.starts_with("log ") {
+                    let mut path = input.trim()[4..].trim();
+                    // A checkpoint name after the file name picks the branch to write.
+                    let moves = match path.rsplit_once(" @") {
+                        Some((file, name)) => {
+                            path = file.trim_end();
+                            self.history.branch_through(name)
+                                .ok_or_else(|| Error::NoSuchCheckpoint(name.to_owned()))?
+                        }
+                        None => self.history.moves(),
+                    };
+                    let mut f = File::create(path)?;
+                    let header = LogHeader::new(self.state.game_number(), self.state.rules(),
+                        self.outcome(&moves));
+                    movelog::write_log(&mut f, &header, &moves)?;
+                    self.ui.write("log file written");
+                    continue;
+                }
+                if lc.starts_with("mark ") {
+                    match self.mark(input.trim()[5..].trim()) {
+                        Ok(()) => self.ui.render(&self.state),
+                        Err(e) => self.ui.write(&e.to_string()),
+                    }
+                    continue;
+                }
+                if lc == "branches" {
+                    self.show_branches();
+                    continue;
+                }
+                if lc == "save" {
+                    match self.save() {
+                        Ok(()) => self.ui.write("game saved"),
+                        Err(e) => self.ui.write(&format!("unable to save game: {}", e)),
+                    }
+                    continue;
+                }
+                if lc == "resume" {
+                    let saved = match SavedGame::load(&self.data_dir()?)? {
+                        Some(saved) => saved,
+                        None => return Err(Error::NoSavedGame),
+                    };
+                    if !self.saved && !self.journal.is_empty() {
+                        self.record_result();
+                    }
+                    self.resume(saved)?;
+                    self.ui.render(&self.state);
+                    continue;
+                }
+                if lc == "new" {
+                    self.record_result();
+                    self.start(random_game_number());
+                    self.ui.render(&self.state);
+                    continue;
+                }
+                if lc == "scores" {
+                    let scores = HighScores::load(&self.data_dir()?)?;
+                    self.ui.show_text(&scores.format_table(&self.state.rules()));
+                    self.ui.render(&self.state);
+                    continue;
+                }
+                if lc == "stats" {
+                    let results = stats::load(&self.data_dir()?)?;
+                    self.ui.show_text(&Summary::new(&results).to_string());
+                    self.ui.render(&self.state);
+                    continue;
+                }
+                if lc == "hint" {
+                    match hint::hint(&self.state) {
+                        Some(action) => {
+                            self.ui.set_hint(Some(action.clone()));
+                            self.ui.render(&self.state);
+                            self.ui.write(&format!("try {}", action));
+                        }
+                        None => self.ui.write("no useful moves left"),
+                    }
+                    continue;
+                }
+            }
+
+            break Ok(input);
+        }
+    }
+
+    pub fn main_loop(&mut self) {
+        loop {
+            self.ui.render(&self.state);
+
+            let input = match self.get_input_text() {
+                Err(e) => {
+                    self.input_error(&e);
+                    continue;
+                }
+                Ok(Some(input)) => input,
+                Ok(None) => {
+                    self.suspend();
+                    return;
+                }
+            };
+
+            let lc = input.trim().to_ascii_lowercase();
+            if lc == "autocomplete" || lc == "undo" || lc == "redo" || lc.starts_with("goto ") {
+                let result = match lc.as_str() {
+                    "autocomplete" => self.autocomplete(),
+                    "undo" => self.undo(),
+                    "redo" => self.redo(),
+                    _ => self.goto(input.trim()[5..].trim()),
+                };
+                if let Err(e) = result {
+                    self.input_error(&e);
+                    continue;
+                }
+            } else {
+                let action = match input.trim().parse::<Action>() {
+                    Ok(action) => action,
+                    Err(e) => {
+                        self.input_error(&e.into());
+                        continue;
+                    }
+                };
+
+                match action {
+                    Action::Quit => {
+                        self.suspend();
+                        break;
+                    }
+                    Action::Help => {
+                        self.ui.halp();
+                        continue;
+                    }
+                    _ => (),
+                }
+
+                if let Err(e) = self.play(action) {
+                    self.input_error(&e);
+                    continue;
+                }
+
+                if self.autocomplete_enabled && self.state.can_autocomplete() {
+                    if let Err(e) = self.autocomplete() {
+                        self.input_error(&e);
+                    }
+                }
+            }
+
+            let status = self.state.status();
+            if status != GameStatus::InProgress {
+                if status == GameStatus::Won {
+                    self.state.award_time_bonus(self.start_time.elapsed());
+                }
+                self.ui.render(&self.state);
+                self.record_result();
+                if status == GameStatus::Won {
+                    self.record_high_score();
+                }
+                match self.ui.end_screen(&self.state, status) {
+                    EndChoice::NewDeal => self.start(random_game_number()),
+                    EndChoice::Replay => self.start(self.state.game_number()),
+                    EndChoice::Quit => break,
+                }
+            } else if self.state.is_dead_end() {
+                self.ui.write("nothing left to play; type 'new' for a new deal");
+            }
+        }
+    }
+
+    /// Start replaying the moves from a log, which must be for the current game.
+    pub fn start_replay(&mut self, replay: Replay, warnings: &[String]) {
+        self.replay = Some(replay);
+        if !warnings.is_empty() {
+            self.ui.write(&format!("warning: {}", warnings.join("; ")));
+        }
+    }
+
+    /// Watch the game in a log file being played, in the replay viewer. This doesn't change the
+    /// game being played.
+    fn view(&mut self, path: &str) -> Result<(), Error> {
+        let (mut viewer, warnings) = Viewer::open(path)?;
+        if !warnings.is_empty() {
+            self.ui.write(&format!("warning: {}", warnings.join("; ")));
+            self.ui.pause(2000);
+        }
+        self.ui.view(&mut viewer);
+        Ok(())
+    }
+
+    /// Tell the player about a problem with the last input. If it came from a log being replayed,
+    /// stop replaying, since the moves after it won't make sense.
+    fn input_error(&mut self, e: &Error) {
+        match self.replay_line.take() {
+            Some(line) => {
+                self.replay = None;
+                self.ui.write(&format!("log line {}: {}", line, e));
+            }
+            None => self.ui.write(&e.to_string()),
+        }
+    }
+
+    /// How the game stands after making some moves from the start.
+    fn outcome(&self, moves: &[Action]) -> GameStatus {
+        let mut state = deal(self.state.game_number(), self.state.rules());
+        for action in moves {
+            state.apply_action(action).expect("illegal move in history");
+        }
+        state.status()
+    }
+
+    /// Apply a move to the game, keeping track of it for undo and the log.
+    fn play(&mut self, action: Action) -> Result<(), Error> {
+        self.apply(action.clone())?;
+        self.journal.push(Command::Play(action));
+        Ok(())
+    }
+
+    fn apply(&mut self, action: Action) -> Result<(), Error> {
+        let record = self.state.apply_action(&action)?;
+        self.ui.set_hint(None);
+        self.undo.push(record);
+        self.history.play(action);
+        self.moves_made += 1;
+        Ok(())
+    }
+
+    /// Take back the last move, at the cost of the undo penalty.
+    fn undo(&mut self) -> Result<(), Error> {
+        let record = self.rewind()?;
+        self.state.undo_move(&record);
+        self.journal.push(Command::Undo);
+        Ok(())
+    }
+
+    /// Go back a move in the history, returning the record for taking it back on the board.
+    fn rewind(&mut self) -> Result<UndoRecord, Error> {
+        self.ui.set_hint(None);
+        let record = self.undo.pop().ok_or(Error::NothingToUndo)?;
+        self.history.back();
+        Ok(record)
+    }
+
+    /// Make the last move that was undone again.
+    fn redo(&mut self) -> Result<(), Error> {
+        let action = self.history.redo_move().ok_or(Error::NothingToRedo)?.clone();
+        self.apply(action)?;
+        self.journal.push(Command::Redo);
+        Ok(())
+    }
+
+    /// Name the current position as a checkpoint to come back to with goto.
+    fn mark(&mut self, name: &str) -> Result<(), Error> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(Error::BadCheckpointName);
+        }
+        self.history.mark(name);
+        self.journal.push(Command::Mark(name.to_owned()));
+        Ok(())
+    }
+
+    /// Go to a checkpoint, by undoing moves back to where its branch split off from this one, and
+    /// then making the moves in its branch. The position was already reached once, so there's no
+    /// undo penalty for the moves taken back.
+    fn goto(&mut self, name: &str) -> Result<(), Error> {
+        let (back, forward) = self.history.route(name)
+            .ok_or_else(|| Error::NoSuchCheckpoint(name.to_owned()))?;
+        for _ in 0 .. back {
+            let record = self.rewind()?;
+            self.state.undo_action(&record);
+        }
+        for action in forward {
+            self.apply(action)?;
+        }
+        self.journal.push(Command::Goto(name.to_owned()));
+        Ok(())
+    }
+
+    /// List the ways the game has continued from here.
+    fn show_branches(&mut self) {
+        let branches = self.history.branches();
+        if branches.is_empty() {
+            self.ui.write("no moves have been made from here");
+            return;
+        }
+        let mut text = String::from("Branches from here (redo follows the last one):\n\n");
+        for (i, branch) in branches.iter().enumerate() {
+            let first = branch.moves[0];
+            text += &format!("{}. {} ({} move{})", i + 1, first, branch.moves.len(),
+                if branch.moves.len() == 1 { "" } else { "s" });
+            if !branch.marks.is_empty() {
+                text += &format!(", marked {}", branch.marks.join(", "));
+            }
+            text.push('\n');
+        }
+        self.ui.show_text(&text);
+        self.ui.render(&self.state);
+    }
+
+    /// Once there's nothing left to do but move cards to the foundation, do that, showing each
+    /// move as it's made.
+    fn autocomplete(&mut self) -> Result<(), Error> {
+        if !self.state.can_autocomplete() {
+            return Err(Error::CantAutocomplete);
+        }
+        while let Some(action) = self.state.autocomplete_move() {
+            self.play(action)?;
+            self.ui.render(&self.state);
+            self.ui.pause(AUTOCOMPLETE_DELAY_MS);
+        }
+        Ok(())
+    }
+
+    /// Keep saved games, statistics, and high scores in a directory other than the player's data
+    /// directory.
+    pub fn set_data_dir(&mut self, dir: PathBuf) {
+        self.data_dir = Some(dir);
+    }
+
+    /// Whether to autocomplete automatically as soon as it's possible.
+    pub fn set_autocomplete(&mut self, enabled: bool) {
+        self.autocomplete_enabled = enabled;
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.state.rules()
+    }
+
+    pub fn set_bankroll(&mut self, bankroll: Option<i32>) {
+        self.bankroll = bankroll;
+        self.ui.set_bankroll(bankroll);
+    }
+
+    /// The frontend the game is being played through.
+    pub fn frontend(&self) -> &F {
+        &self.ui
+    }
+
+    /// Returns the results of all the games played, the final Vegas bankroll, and the number of
+    /// the game that was saved to be resumed later, if any.
+    pub fn end(self) -> (Vec<GameResult>, Option<i32>, Option<u64>) {
+        (self.results, self.bankroll, self.suspended)
+    }
+}
@@ -1,6 +1,14 @@
 use crate::action::{Action, Destination, Source};
+use crate::error::{MoveError, MoveErrorKind};
+use crate::rules::Rules;
+use crate::scoring::ScoreEvent;
+use std::collections::HashSet;
+use std::time::Duration;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// How many moves ahead `is_dead_end` looks for a way to make progress.
+const DEAD_END_DEPTH: usize = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Suit {
     Spades,
     Clubs,
@@ -41,7 +49,7 @@ impl Suit {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum Rank {
     Ace = 1,
@@ -83,7 +91,7 @@ impl std::fmt::Display for Rank {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Card {
     pub suit: Suit,
     pub rank: Rank,
@@ -95,49 +103,85 @@ impl std::fmt::Debug for Card {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Facing {
     Up,
     Down,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Stock {
     stock: Vec<Card>,
     waste: Vec<Card>,
+    draw_count: usize,
+    passes: u32,
 }
 
 impl Stock {
-    pub fn new(cards: Vec<Card>) -> Self {
+    pub fn new(cards: Vec<Card>, draw_count: usize) -> Self {
         Self {
             stock: cards,
             waste: vec![],
+            draw_count,
+            passes: 1,
         }
     }
 
-    pub fn draw_three(&mut self) -> bool {
+    /// Turn over the next `draw_count` cards from the stock into the waste, or if the stock is
+    /// empty, recycle the waste back into the stock. Returns true if the waste was recycled.
+    pub fn draw(&mut self) -> bool {
         if self.stock.is_empty() {
+            if self.waste.is_empty() {
+                return false;
+            }
             self.stock.extend(self.waste.drain(..).rev());
+            self.passes += 1;
             true
         } else {
-            let end = self.stock.len().saturating_sub(3);
+            let end = self.stock.len().saturating_sub(self.draw_count);
             self.waste.extend(self.stock.drain(end..).rev());
             false
         }
     }
 
+    pub fn draw_count(&self) -> usize {
+        self.draw_count
+    }
+
+    /// Which pass through the stock this is, starting at 1.
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
     pub fn stock_size(&self) -> usize {
         self.stock.len()
     }
 
     pub fn showing(&self) -> &[Card] {
-        let end = self.waste.len().saturating_sub(3);
+        let end = self.waste.len().saturating_sub(self.draw_count);
         &self.waste[end..]
     }
 
     pub fn take(&mut self) -> Option<Card> {
         self.waste.pop()
     }
+
+    /// Put a card taken from the waste back.
+    pub(crate) fn put_back(&mut self, card: Card) {
+        self.waste.push(card);
+    }
+
+    /// Reverse a draw which turned over `count` cards.
+    pub(crate) fn undo_draw(&mut self, count: usize) {
+        let end = self.waste.len() - count;
+        self.stock.extend(self.waste.drain(end..).rev());
+    }
+
+    /// Reverse a draw which recycled the waste back into the stock.
+    pub(crate) fn undo_recycle(&mut self) {
+        self.waste.extend(self.stock.drain(..).rev());
+        self.passes -= 1;
+    }
 }
 
 #[cfg(test)]
@@ -151,43 +195,130 @@ mod test_stock {
             .collect()
     }
 
-    #[test]
-    fn test_stock() {
+    fn five_clubs() -> Vec<Card> {
         use Rank::*;
         use Suit::*;
-        let mut stock = Stock::new(vec![
+        vec![
             Card { rank: Ace, suit: Clubs },
             Card { rank: N2,  suit: Clubs },
             Card { rank: N3,  suit: Clubs },
             Card { rank: N4,  suit: Clubs },
             Card { rank: N5,  suit: Clubs },
-        ]);
+        ]
+    }
+
+    #[test]
+    fn test_stock() {
+        let mut stock = Stock::new(five_clubs(), 3);
         assert!(stock.showing().is_empty());
         assert_eq!(5, stock.stock_size());
 
-        assert_eq!(false, stock.draw_three());
+        assert!(!stock.draw());
         assert_eq!(&[5, 4, 3][..], waste(&stock));
 
-        assert_eq!(false, stock.draw_three());
+        assert!(!stock.draw());
         assert_eq!(&[3, 2, 1][..], waste(&stock));
 
-        assert_eq!(true, stock.draw_three());
+        assert_eq!(1, stock.passes());
+        assert!(stock.draw());
         assert!(stock.showing().is_empty());
+        assert_eq!(2, stock.passes());
 
-        assert_eq!(false, stock.draw_three());
+        assert!(!stock.draw());
         assert_eq!(&[5, 4, 3][..], waste(&stock));
 
         assert_eq!(Some(3), stock.take().map(|card| card.rank as u8));
         assert_eq!(&[5, 4][..], waste(&stock));
 
-        assert_eq!(false, stock.draw_three());
+        assert!(!stock.draw());
         assert_eq!(&[4, 2, 1][..], waste(&stock));
     }
+
+    #[test]
+    fn test_stock_draw_one() {
+        let mut stock = Stock::new(five_clubs(), 1);
+        assert!(stock.showing().is_empty());
+
+        assert!(!stock.draw());
+        assert_eq!(&[5][..], waste(&stock));
+        assert_eq!(4, stock.stock_size());
+
+        assert!(!stock.draw());
+        assert_eq!(&[4][..], waste(&stock));
+
+        assert_eq!(Some(4), stock.take().map(|card| card.rank as u8));
+        assert_eq!(&[5][..], waste(&stock));
+
+        for _ in 0 .. 3 {
+            assert!(!stock.draw());
+        }
+        assert_eq!(&[1][..], waste(&stock));
+        assert_eq!(0, stock.stock_size());
+
+        assert!(stock.draw());
+        assert!(stock.showing().is_empty());
+        assert_eq!(4, stock.stock_size());
+
+        assert!(!stock.draw());
+        assert_eq!(&[5][..], waste(&stock));
+    }
+
+    #[test]
+    fn test_stock_empty() {
+        let mut stock = Stock::new(vec![Card { rank: Rank::Ace, suit: Suit::Spades }], 3);
+        assert!(!stock.draw());
+        stock.take();
+
+        // Nothing to recycle, so this doesn't count as a pass.
+        assert!(!stock.draw());
+        assert_eq!(1, stock.passes());
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameStatus {
+    InProgress,
+    Won,
+    /// The game isn't won, but there are no legal moves left to make.
+    NoMovesLeft,
+}
+
+/// A place cards can be moved from or to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pile {
+    Waste,
+    Foundation(usize),
+    Tableau(usize),
+}
+
+/// What applying an action changed in the game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Nothing changed.
+    None,
+    /// This many cards were turned over from the stock into the waste.
+    Drew(usize),
+    /// The waste was turned back over to make the stock again.
+    Recycled,
+    /// The bottom card of a tableau column was turned face up.
+    Flipped { column: usize },
+    /// Cards were moved from the end of one pile to the end of another.
+    Moved { from: Pile, to: Pile, count: usize },
+}
+
+/// Everything needed to reverse an action with `GameState::undo_action`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoRecord {
+    pub change: Change,
+    /// How much the score changed by. This is kept rather than worked out again, because some
+    /// scoring doesn't let the score go below zero.
+    pub score_delta: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GameState {
     game_number: u64,
+    rules: Rules,
     stock: Stock,
     foundation: [Vec<Card>; 4],
     tableau: [Vec<(Card, Facing)>; 7],
@@ -195,7 +326,7 @@ pub struct GameState {
 }
 
 impl GameState {
-    pub fn new(game_number: u64, mut cards: Vec<Card>) -> Self {
+    pub fn new(game_number: u64, rules: Rules, mut cards: Vec<Card>) -> Self {
         let mut tableau = <[Vec<(Card, Facing)>; 7]>::default();
 
         for (i, column) in tableau.iter_mut().enumerate() {
@@ -207,15 +338,29 @@ impl GameState {
 
         Self {
             game_number,
-            stock: Stock::new(cards),
+            rules,
+            stock: Stock::new(cards, rules.draw_count),
             foundation: Default::default(),
             tableau,
-            score: 0,
+            score: rules.scoring.policy().initial_score(),
         }
     }
 
-    pub fn draw_three(&mut self) -> bool {
-        self.stock.draw_three()
+    pub fn draw(&mut self) -> bool {
+        self.stock.draw()
+    }
+
+    pub fn draw_count(&self) -> usize {
+        self.stock.draw_count()
+    }
+
+    pub fn passes(&self) -> u32 {
+        self.stock.passes()
+    }
+
+    /// Whether drawing would recycle the waste, but the pass limit doesn't allow it.
+    pub fn stock_exhausted(&self) -> bool {
+        self.stock.stock_size() == 0 && !self.rules.can_recycle(self.stock.passes())
     }
 
     pub fn stock_size(&self) -> usize {
@@ -234,21 +379,21 @@ impl GameState {
         self.foundation[idx].last()
     }
 
-    fn can_stack_tableau(&self, card: &Card, column: usize) -> Result<(), &'static str> {
-        match self.tableau.get(column).ok_or("no such column")?.last() {
+    fn can_stack_tableau(&self, card: &Card, column: usize) -> Result<(), MoveErrorKind> {
+        match self.tableau.get(column).ok_or(MoveErrorKind::NoSuchPile)?.last() {
             None => {
                 if card.rank == Rank::King {
                     Ok(())
                 } else {
-                    Err("only King can go on empty tableau space")
+                    Err(MoveErrorKind::NotKing)
                 }
             }
-            Some((_, Facing::Down)) => Err("cannot place on face-down card"),
+            Some((_, Facing::Down)) => Err(MoveErrorKind::OntoFaceDown),
             Some((parent, Facing::Up)) => {
                 if parent.suit.color() == card.suit.color() {
-                    Err("cards must differ in color")
+                    Err(MoveErrorKind::SameColor)
                 } else if parent.rank.value() != card.rank.value() + 1 {
-                    Err("card value is not one higher than that being placed")
+                    Err(MoveErrorKind::NotOneLower)
                 } else {
                     Ok(())
                 }
@@ -256,20 +401,20 @@ impl GameState {
         }
     }
 
-    fn can_stack_foundation(&self, card: &Card, column: usize) -> Result<(), &'static str> {
-        match self.foundation.get(column).ok_or("no such column")?.last() {
+    fn can_stack_foundation(&self, card: &Card, column: usize) -> Result<(), MoveErrorKind> {
+        match self.foundation.get(column).ok_or(MoveErrorKind::NoSuchPile)?.last() {
             None => {
                 if card.rank == Rank::Ace {
                     Ok(())
                 } else {
-                    Err("only Ace can go on empty foundation space")
+                    Err(MoveErrorKind::NotAce)
                 }
             }
             Some(parent) => {
                 if parent.suit != card.suit {
-                    Err("cards must match in suit")
+                    Err(MoveErrorKind::WrongSuit)
                 } else if parent.rank.value() + 1 != card.rank.value() {
-                    Err("card value is not one lower than that being placed")
+                    Err(MoveErrorKind::NotOneHigher)
                 } else {
                     Ok(())
                 }
@@ -277,11 +422,83 @@ impl GameState {
         }
     }
 
-    pub fn apply_action(&mut self, action: &Action) -> Result<(), &'static str> {
+    /// Make a move, returning a record of what it changed, which can be used to undo it.
+    pub fn apply_action(&mut self, action: &Action) -> Result<UndoRecord, MoveError> {
+        let score = self.score;
+        match self.apply_change(action) {
+            Ok(change) => Ok(UndoRecord { change, score_delta: self.score - score }),
+            Err(kind) => {
+                let (source, destination) = match action {
+                    Action::Move(src, dest) => (Some(src.clone()), Some(dest.clone())),
+                    Action::QuickMove(src) => (Some(src.clone()), None),
+                    Action::Quit | Action::Help | Action::Draw => (None, None),
+                };
+                // Nothing has moved, so the card is still where it started. Face-down cards are
+                // left out, so that the error doesn't give them away.
+                let card = source.as_ref().and_then(|src| self.get_src_card_ref(src).ok()).copied();
+                Err(MoveError { kind, card, source, destination })
+            }
+        }
+    }
+
+    /// Take back an action for the player, which must have been the last one applied, charging
+    /// whatever the scoring policy takes away for an undo.
+    pub fn undo_move(&mut self, record: &UndoRecord) {
+        self.undo_action(record);
+        self.score_event(ScoreEvent::Undo);
+    }
+
+    /// Reverse an action, which must have been the last one applied.
+    pub fn undo_action(&mut self, record: &UndoRecord) {
+        match record.change {
+            Change::None => (),
+            Change::Drew(count) => self.stock.undo_draw(count),
+            Change::Recycled => self.stock.undo_recycle(),
+            Change::Flipped { column } => {
+                self.tableau[column].last_mut().expect("no card to flip back").1 = Facing::Down;
+            }
+            Change::Moved { from, to, count } => {
+                let cards = match to {
+                    Pile::Waste => unreachable!("cards can't be moved to the waste"),
+                    Pile::Foundation(idx) => {
+                        let end = self.foundation[idx].len() - count;
+                        self.foundation[idx].drain(end..).map(|card| (card, Facing::Up)).collect()
+                    }
+                    Pile::Tableau(column) => {
+                        let end = self.tableau[column].len() - count;
+                        self.tableau[column].split_off(end)
+                    }
+                };
+                for (card, facing) in cards {
+                    match from {
+                        Pile::Waste => self.stock.put_back(card),
+                        Pile::Foundation(idx) => self.foundation[idx].push(card),
+                        Pile::Tableau(column) => self.tableau[column].push((card, facing)),
+                    }
+                }
+            }
+        }
+        self.score -= record.score_delta;
+    }
+
+    fn apply_change(&mut self, action: &Action) -> Result<Change, MoveErrorKind> {
+        let moved = |from, to, count| Change::Moved { from, to, count };
         match action {
-            Action::Quit | Action::Help => (),
+            Action::Quit | Action::Help => Ok(Change::None),
             Action::Draw => {
-                self.draw_three();
+                if self.stock_exhausted() {
+                    return Err(MoveErrorKind::NoPassesLeft);
+                }
+                if self.stock.stock_size() == 0 && self.stock.showing().is_empty() {
+                    return Err(MoveErrorKind::StockEmpty);
+                }
+                let count = self.stock.stock_size().min(self.stock.draw_count());
+                if self.draw() {
+                    self.score_event(ScoreEvent::RecycleWaste);
+                    Ok(Change::Recycled)
+                } else {
+                    Ok(Change::Drew(count))
+                }
             }
             Action::Move(src, dest) => {
                 let card_ref = self.get_src_card_ref(src)?;
@@ -290,10 +507,16 @@ impl GameState {
                         self.can_stack_tableau(card_ref, column)?;
                     }
                     Destination::Foundation(column) => {
-                        if let Source::Tableau { column: src_col, row: src_row } = *src {
-                            if !self.is_bottom_of_tableau(src_col, src_row) {
-                                return Err("can only pop off the bottom card of a stack");
+                        match *src {
+                            Source::Tableau { column: src_col, row: src_row } => {
+                                if !self.is_bottom_of_tableau(src_col, src_row) {
+                                    return Err(MoveErrorKind::NotBottomCard);
+                                }
                             }
+                            Source::Foundation(_) => {
+                                return Err(MoveErrorKind::AlreadyOnFoundation);
+                            }
+                            Source::Waste => (),
                         }
                         self.can_stack_foundation(card_ref, column)?;
                     }
@@ -301,26 +524,38 @@ impl GameState {
 
                 match (src, dest) {
                     (Source::Waste, &Destination::Foundation(column)) => {
-                        self.score += 10;
+                        self.score_event(ScoreEvent::WasteToFoundation);
                         self.foundation[column].push(self.stock.take().unwrap());
+                        Ok(moved(Pile::Waste, Pile::Foundation(column), 1))
                     }
                     (Source::Waste, &Destination::Tableau(column)) => {
-                        self.score += 5;
+                        self.score_event(ScoreEvent::WasteToTableau);
                         self.tableau[column].push((self.stock.take().unwrap(), Facing::Up));
+                        Ok(moved(Pile::Waste, Pile::Tableau(column), 1))
                     }
                     (&Source::Tableau { column, row }, &Destination::Foundation(idx)) => {
-                        self.score += 10;
+                        self.score_event(ScoreEvent::TableauToFoundation);
                         self.foundation[idx].push(self.tableau[column].remove(row).0);
+                        Ok(moved(Pile::Tableau(column), Pile::Foundation(idx), 1))
                     }
                     (&Source::Tableau { column: src_col, row: src_row },
                         &Destination::Tableau(dst_col)) =>
                     {
+                        let count = self.tableau[src_col].len() - src_row;
                         while self.tableau[src_col].get(src_row).is_some() {
                             let (card, facing) = self.tableau[src_col].remove(src_row);
                             self.tableau[dst_col].push((card, facing));
                         }
+                        Ok(moved(Pile::Tableau(src_col), Pile::Tableau(dst_col), count))
                     }
-                };
+                    (&Source::Foundation(idx), &Destination::Tableau(column)) => {
+                        self.score_event(ScoreEvent::FoundationToTableau);
+                        let card = self.foundation[idx].pop().unwrap();
+                        self.tableau[column].push((card, Facing::Up));
+                        Ok(moved(Pile::Foundation(idx), Pile::Tableau(column), 1))
+                    }
+                    (Source::Foundation(_), Destination::Foundation(_)) => unreachable!(),
+                }
             }
             Action::QuickMove(src) => {
                 // Unfortunately a big duplication of get_src_card_ref...
@@ -330,19 +565,25 @@ impl GameState {
                     {
                         if self.is_bottom_of_tableau(column, row) {
                             // flip card
-                            self.score += 5;
+                            self.score_event(ScoreEvent::FlipCard);
                             self.tableau[column][row].1 = Facing::Up;
-                            return Ok(());
+                            return Ok(Change::Flipped { column });
                         }
                     }
                 }
 
                 let card_ref = self.get_src_card_ref(src)?;
 
-                if let Source::Tableau { column, row } = *src {
-                    if !self.is_bottom_of_tableau(column, row) {
-                        return Err("can only pop off the bottom card of a stack");
+                match *src {
+                    Source::Tableau { column, row } => {
+                        if !self.is_bottom_of_tableau(column, row) {
+                            return Err(MoveErrorKind::NotBottomCard);
+                        }
                     }
+                    Source::Foundation(_) => {
+                        return Err(MoveErrorKind::NeedsDestination);
+                    }
+                    Source::Waste => (),
                 }
 
                 let mut foundation_idx = None;
@@ -355,26 +596,47 @@ impl GameState {
 
                 match foundation_idx {
                     Some(i) => {
-                        self.score += 10;
-                        self.foundation[i].push(match *src {
-                            Source::Waste => self.stock.take().unwrap(),
+                        let (card, from) = match *src {
+                            Source::Waste => {
+                                self.score_event(ScoreEvent::WasteToFoundation);
+                                (self.stock.take().unwrap(), Pile::Waste)
+                            }
                             Source::Tableau { column, row } => {
-                                self.tableau[column].remove(row).0
+                                self.score_event(ScoreEvent::TableauToFoundation);
+                                (self.tableau[column].remove(row).0, Pile::Tableau(column))
                             }
-                        });
+                            Source::Foundation(_) => unreachable!(),
+                        };
+                        self.foundation[i].push(card);
+                        Ok(moved(from, Pile::Foundation(i), 1))
                     }
-                    None => return Err("can't put that on any of the foundation stacks"),
+                    None => Err(MoveErrorKind::NoFoundationFits),
                 }
             }
         }
-        Ok(())
     }
 
-    fn get_src_card_ref(&self, location: &Source) -> Result<&Card, &'static str> {
+    fn score_event(&mut self, event: ScoreEvent) {
+        self.score = self.rules.scoring.policy().score_event(self.score, event, &self.rules);
+    }
+
+    /// Award any bonus the scoring policy gives for winning in the given amount of time.
+    pub(crate) fn award_time_bonus(&mut self, elapsed: Duration) {
+        self.score += self.rules.scoring.policy().time_bonus(elapsed);
+    }
+
+    fn get_src_card_ref(&self, location: &Source) -> Result<&Card, MoveErrorKind> {
         match location {
             Source::Waste => match self.stock.showing().last() {
                 Some(card) => Ok(card),
-                None => Err("waste is empty"),
+                None => Err(MoveErrorKind::WasteEmpty),
+            },
+            Source::Foundation(idx) => match self.foundation
+                .get(*idx)
+                .and_then(|cards| cards.last())
+            {
+                Some(card) => Ok(card),
+                None => Err(MoveErrorKind::FoundationEmpty),
             },
             Source::Tableau { column, row } => match self.tableau
                 .get(*column)
@@ -383,8 +645,8 @@ impl GameState {
                 Some((card, Facing::Up)) => {
                     Ok(card)
                 }
-                Some((_, Facing::Down)) => Err("cannot move face-down card"),
-                None => Err("no card there"),
+                Some((_, Facing::Down)) => Err(MoveErrorKind::FaceDown),
+                None => Err(MoveErrorKind::NoCard),
             }
         }
     }
@@ -395,11 +657,532 @@ impl GameState {
             .is_none()
     }
 
+    pub fn is_won(&self) -> bool {
+        self.foundation.iter().all(|stack| stack.len() == Rank::all().len())
+    }
+
+    /// Whether all that's left to do is move cards to the foundation: the stock and waste are
+    /// empty and all the cards in the tableau are face up.
+    pub fn can_autocomplete(&self) -> bool {
+        self.stock.stock_size() == 0
+            && self.stock.showing().is_empty()
+            && self.tableau.iter()
+                .flatten()
+                .all(|(_, facing)| matches!(facing, Facing::Up))
+    }
+
+    /// The next move to make when autocompleting: the lowest card that can go to the foundation.
+    pub fn autocomplete_move(&self) -> Option<Action> {
+        self.tableau.iter()
+            .enumerate()
+            .filter_map(|(column, cards)| {
+                let (card, _) = cards.last()?;
+                if (0 .. 4).any(|i| self.can_stack_foundation(card, i).is_ok()) {
+                    Some((card.rank.value(), column, cards.len() - 1))
+                } else {
+                    None
+                }
+            })
+            .min()
+            .map(|(_, column, row)| Action::QuickMove(Source::Tableau { column, row }))
+    }
+
+    /// Whether no amount of drawing from the stock will ever make progress possible again.
+    /// Moves which only shuffle cards around, like moving a card back off the foundation or a run
+    /// between two columns, don't count unless they lead to progress within a move or two.
+    pub fn is_dead_end(&self) -> bool {
+        let mut state = self.clone();
+        let mut seen = HashSet::new();
+        loop {
+            if !seen.insert(state.position_key()) {
+                // Drawing has come back around to a position already checked.
+                return true;
+            }
+            let start = state.clone();
+            if state.can_progress(&start, DEAD_END_DEPTH) {
+                return false;
+            }
+            if state.apply_action(&Action::Draw).is_err() {
+                return true;
+            }
+        }
+    }
+
+    /// Whether progress can be made from here with up to `depth` moves, not counting draws.
+    fn can_progress(&mut self, start: &GameState, depth: usize) -> bool {
+        if self.made_progress(start) {
+            return true;
+        }
+        if depth == 0 {
+            return false;
+        }
+        for action in self.legal_moves() {
+            if action == Action::Draw {
+                continue;
+            }
+            if let Ok(record) = self.apply_action(&action) {
+                let progress = self.can_progress(start, depth - 1);
+                self.undo_action(&record);
+                if progress {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Whether this position is further along than an earlier one: more cards on the foundation,
+    /// fewer in the stock and waste, more empty columns, or fewer face-down cards, or fewer which
+    /// are still covered up.
+    fn made_progress(&self, before: &GameState) -> bool {
+        let foundation = |state: &GameState| state.foundation.iter().map(Vec::len).sum::<usize>();
+        let stock = |state: &GameState| state.stock.stock.len() + state.stock.waste.len();
+        let empty = |state: &GameState| {
+            state.tableau.iter().filter(|cards| cards.is_empty()).count()
+        };
+        let face_down = |state: &GameState, skip| {
+            state.tableau.iter()
+                .map(|cards| {
+                    cards.iter().rev().skip(skip).filter(|(_, f)| *f == Facing::Down).count()
+                })
+                .sum::<usize>()
+        };
+        foundation(self) > foundation(before)
+            || stock(self) < stock(before)
+            || empty(self) > empty(before)
+            || face_down(self, 0) < face_down(before, 0)
+            || face_down(self, 1) < face_down(before, 1)
+    }
+
+    pub fn status(&self) -> GameStatus {
+        if self.is_won() {
+            GameStatus::Won
+        } else if self.has_legal_move() {
+            GameStatus::InProgress
+        } else {
+            GameStatus::NoMovesLeft
+        }
+    }
+
+    fn has_legal_move(&self) -> bool {
+        !self.legal_moves().is_empty()
+    }
+
+    /// Every move that can be made in the current position. Moves to the foundation are listed
+    /// with their destination, rather than as quick moves, and flips as quick moves.
+    pub fn legal_moves(&self) -> Vec<Action> {
+        let mut moves = vec![];
+
+        if !self.stock_exhausted() && (self.stock.stock_size() != 0 || !self.waste().is_empty()) {
+            moves.push(Action::Draw);
+        }
+
+        let mut sources = vec![];
+        if let Some(card) = self.waste().last() {
+            sources.push((Source::Waste, card, true));
+        }
+        for (idx, cards) in self.foundation.iter().enumerate() {
+            if let Some(card) = cards.last() {
+                sources.push((Source::Foundation(idx), card, false));
+            }
+        }
+        for (column, cards) in self.tableau.iter().enumerate() {
+            for (row, (card, facing)) in cards.iter().enumerate() {
+                let src = Source::Tableau { column, row };
+                let bottom = row + 1 == cards.len();
+                match facing {
+                    Facing::Up => sources.push((src, card, bottom)),
+                    Facing::Down if bottom => moves.push(Action::QuickMove(src)),
+                    Facing::Down => (),
+                }
+            }
+        }
+
+        for (src, card, can_go_to_foundation) in sources {
+            if can_go_to_foundation {
+                for idx in 0 .. 4 {
+                    if self.can_stack_foundation(card, idx).is_ok() {
+                        moves.push(Action::Move(src.clone(), Destination::Foundation(idx)));
+                    }
+                }
+            }
+            for column in 0 .. 7 {
+                if let Source::Tableau { column: src_col, .. } = src {
+                    if src_col == column {
+                        continue;
+                    }
+                }
+                if self.can_stack_tableau(card, column).is_ok() {
+                    moves.push(Action::Move(src.clone(), Destination::Tableau(column)));
+                }
+            }
+        }
+
+        moves
+    }
+
+    /// Everything about the position that affects how the game can play out from here, as a
+    /// compact string of bytes. Positions which differ only in the order of the tableau columns or
+    /// foundation piles have the same key, as do positions which differ only in score.
+    pub fn position_key(&self) -> Vec<u8> {
+        // Each card is a byte, with the suit in the high bits and the rank in the low ones, and
+        // the top bit set if it's face down. Piles are ended with a byte no card can be.
+        const END: u8 = 0xff;
+        let card_byte = |card: &Card| (card.suit as u8) << 4 | card.rank.value();
+
+        let mut key = vec![];
+        key.extend(self.stock.stock.iter().map(card_byte));
+        key.push(END);
+        key.extend(self.stock.waste.iter().map(card_byte));
+        key.push(END);
+        if self.rules.pass_limit.is_some() {
+            key.extend_from_slice(&self.stock.passes.to_le_bytes());
+        }
+
+        for &suit in Suit::all() {
+            key.push(self.foundation.iter()
+                .find(|cards| cards.first().map(|card| card.suit) == Some(suit))
+                .map_or(0, |cards| cards.len() as u8));
+        }
+
+        let mut columns = self.tableau.iter()
+            .map(|cards| {
+                cards.iter()
+                    .map(|(card, facing)| match facing {
+                        Facing::Up => card_byte(card),
+                        Facing::Down => card_byte(card) | 0x80,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        columns.sort_unstable();
+        for column in columns {
+            key.extend(column);
+            key.push(END);
+        }
+        key
+    }
+
     pub fn game_number(&self) -> u64 {
         self.game_number
     }
 
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
     pub fn score(&self) -> i32 {
         self.score
     }
 }
+
+#[cfg(test)]
+mod test_game_state {
+    use super::*;
+    use crate::scoring::ScoringMode;
+
+    fn card(rank: Rank, suit: Suit) -> Card {
+        Card { rank, suit }
+    }
+
+    /// A game with nothing in it but what the test puts there.
+    fn empty_game(rules: Rules) -> GameState {
+        GameState {
+            game_number: 0,
+            rules,
+            stock: Stock::new(vec![], rules.draw_count),
+            foundation: Default::default(),
+            tableau: Default::default(),
+            score: 0,
+        }
+    }
+
+    #[test]
+    fn test_move_from_foundation() {
+        use Rank::*;
+        use Suit::*;
+        let mut game = empty_game(Rules { scoring: ScoringMode::Standard, ..Rules::default() });
+        game.score = 100;
+        game.foundation[0] = vec![card(Ace, Hearts), card(N2, Hearts), card(N3, Hearts)];
+        game.tableau[0].push((card(N4, Clubs), Facing::Up));
+        game.tableau[1].push((card(N4, Diamonds), Facing::Up));
+
+        let to_column = |column| Action::Move(Source::Foundation(0), Destination::Tableau(column));
+        let error = game.apply_action(&to_column(1)).unwrap_err();
+        assert_eq!(MoveErrorKind::SameColor, error.kind);
+        assert_eq!(Some(card(N3, Hearts)), error.card);
+        assert_eq!(Some(Destination::Tableau(1)), error.destination);
+        assert_eq!("cards must differ in color", error.to_string());
+        assert_eq!(Err(MoveErrorKind::NotKing),
+            game.apply_action(&to_column(2)).map_err(|e| e.kind));
+        assert!(game.apply_action(&Action::QuickMove(Source::Foundation(0))).is_err());
+        let error = game.apply_action(&Action::Move(Source::Foundation(1), Destination::Tableau(0)))
+            .unwrap_err();
+        assert_eq!(MoveErrorKind::FoundationEmpty, error.kind);
+        assert_eq!(None, error.card);
+
+        assert_eq!(Ok(Change::Moved { from: Pile::Foundation(0), to: Pile::Tableau(0), count: 1 }),
+            game.apply_action(&to_column(0)).map(|record| record.change));
+        assert_eq!(Some(N2), game.foundation(0).map(|card| card.rank));
+        assert_eq!(2, game.tableau(0).len());
+        assert_eq!(85, game.score());
+
+        // and back again
+        let back = Action::QuickMove(Source::Tableau { column: 0, row: 1 });
+        assert_eq!(Ok(Change::Moved { from: Pile::Tableau(0), to: Pile::Foundation(0), count: 1 }),
+            game.apply_action(&back).map(|record| record.change));
+        assert_eq!(Some(N3), game.foundation(0).map(|card| card.rank));
+        assert_eq!(95, game.score());
+    }
+
+    /// Apply an action, check that it changed what was expected, and check that undoing it puts
+    /// everything back the way it was.
+    fn round_trip(game: &mut GameState, action: Action, change: Change) {
+        let before = game.clone();
+        let record = game.apply_action(&action).unwrap();
+        assert_eq!(change, record.change, "{}", action);
+        assert_eq!(change == Change::None, before == *game, "{}", action);
+        game.undo_action(&record);
+        assert_eq!(before, *game, "{}", action);
+    }
+
+    #[test]
+    fn test_undo_action() {
+        use Rank::*;
+        use Suit::*;
+        let rules = Rules { draw_count: 1, scoring: ScoringMode::Standard, ..Rules::default() };
+        let mut game = empty_game(rules);
+        game.score = 100;
+        game.stock = Stock::new(vec![card(Ace, Spades), card(N9, Hearts), card(Ace, Clubs)], 1);
+        game.foundation[0] = vec![card(Ace, Hearts), card(N2, Hearts)];
+        game.tableau[0] = vec![(card(Queen, Spades), Facing::Down), (card(N3, Hearts), Facing::Up)];
+        game.tableau[1] = vec![(card(N4, Spades), Facing::Up), (card(N3, Diamonds), Facing::Down)];
+        game.tableau[2] = vec![(card(Jack, Hearts), Facing::Up), (card(N10, Spades), Facing::Up)];
+        game.tableau[3] = vec![(card(Queen, Clubs), Facing::Up)];
+        game.tableau[4] = vec![(card(N10, Clubs), Facing::Up)];
+        game.tableau[5] = vec![(card(N3, Clubs), Facing::Up)];
+
+        round_trip(&mut game, Action::Quit, Change::None);
+        round_trip(&mut game, Action::Draw, Change::Drew(1));
+        round_trip(&mut game, Action::QuickMove(Source::Tableau { column: 1, row: 1 }),
+            Change::Flipped { column: 1 });
+        round_trip(&mut game,
+            Action::Move(Source::Tableau { column: 2, row: 0 }, Destination::Tableau(3)),
+            Change::Moved { from: Pile::Tableau(2), to: Pile::Tableau(3), count: 2 });
+        round_trip(&mut game,
+            Action::Move(Source::Tableau { column: 0, row: 1 }, Destination::Foundation(0)),
+            Change::Moved { from: Pile::Tableau(0), to: Pile::Foundation(0), count: 1 });
+        round_trip(&mut game, Action::QuickMove(Source::Tableau { column: 0, row: 1 }),
+            Change::Moved { from: Pile::Tableau(0), to: Pile::Foundation(0), count: 1 });
+        round_trip(&mut game,
+            Action::Move(Source::Foundation(0), Destination::Tableau(5)),
+            Change::Moved { from: Pile::Foundation(0), to: Pile::Tableau(5), count: 1 });
+
+        game.apply_action(&Action::Draw).unwrap();
+        round_trip(&mut game, Action::Move(Source::Waste, Destination::Foundation(1)),
+            Change::Moved { from: Pile::Waste, to: Pile::Foundation(1), count: 1 });
+        round_trip(&mut game, Action::QuickMove(Source::Waste),
+            Change::Moved { from: Pile::Waste, to: Pile::Foundation(1), count: 1 });
+
+        game.apply_action(&Action::Draw).unwrap();
+        round_trip(&mut game, Action::Move(Source::Waste, Destination::Tableau(4)),
+            Change::Moved { from: Pile::Waste, to: Pile::Tableau(4), count: 1 });
+
+        game.apply_action(&Action::Draw).unwrap();
+        // Recycling costs 100 points, but the score can't go below zero, so this checks that
+        // undoing gives back what was actually taken.
+        game.score = 30;
+        round_trip(&mut game, Action::Draw, Change::Recycled);
+    }
+
+    #[test]
+    fn test_undo_random_games() {
+        use rand::{Rng, SeedableRng};
+        let mut deck = vec![];
+        for &rank in Rank::all() {
+            for &suit in Suit::all() {
+                deck.push(Card { suit, rank });
+            }
+        }
+        for seed in 0 .. 20 {
+            let mut rand = <rand_pcg::Pcg32 as SeedableRng>::seed_from_u64(seed);
+            for i in 0 .. deck.len() {
+                let j = rand.gen_range(i .. deck.len());
+                deck.swap(i, j);
+            }
+            let rules = Rules { scoring: ScoringMode::Standard, ..Rules::default() };
+            let start = GameState::new(seed, rules, deck.clone());
+            let mut game = start.clone();
+            let mut states = vec![];
+            let mut records = vec![];
+            for _ in 0 .. 200 {
+                let moves = game.legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+                let action = &moves[rand.gen_range(0 .. moves.len())];
+                states.push(game.clone());
+                records.push(game.apply_action(action).unwrap());
+            }
+            while let Some(record) = records.pop() {
+                game.undo_action(&record);
+                assert_eq!(states.pop().unwrap(), game);
+            }
+            assert_eq!(start, game);
+        }
+    }
+
+    #[test]
+    fn test_autocomplete() {
+        use Rank::*;
+        use Suit::*;
+        let mut game = empty_game(Rules::default());
+        game.foundation[0] = vec![card(Ace, Hearts)];
+        game.foundation[1] = vec![card(Ace, Spades)];
+        game.tableau[0] = vec![(card(N3, Spades), Facing::Down), (card(N2, Hearts), Facing::Up)];
+        game.tableau[1] = vec![(card(N3, Hearts), Facing::Up), (card(N2, Spades), Facing::Up)];
+        assert!(!game.can_autocomplete());
+
+        game.tableau[0][0].1 = Facing::Up;
+        assert!(game.can_autocomplete());
+
+        let mut moves = 0;
+        while let Some(action) = game.autocomplete_move() {
+            game.apply_action(&action).unwrap();
+            moves += 1;
+        }
+        assert_eq!(4, moves);
+        assert!(game.tableau.iter().all(|cards| cards.is_empty()));
+        assert_eq!(Some(N3), game.foundation(0).map(|card| card.rank));
+        assert_eq!(Some(N3), game.foundation(1).map(|card| card.rank));
+    }
+
+    #[test]
+    fn test_legal_moves() {
+        use Rank::*;
+        use Suit::*;
+        let mut game = empty_game(Rules::default());
+        game.stock = Stock::new(vec![card(Ace, Diamonds), card(N2, Clubs)], 3);
+        game.apply_action(&Action::Draw).unwrap();
+        game.foundation[0] = vec![card(Ace, Clubs)];
+        game.tableau[0] = vec![(card(N9, Spades), Facing::Down)];
+        game.tableau[1] = vec![(card(N4, Hearts), Facing::Down), (card(N3, Hearts), Facing::Up)];
+        game.tableau[2] = vec![(card(King, Clubs), Facing::Up)];
+        game.tableau[3] = vec![(card(N2, Hearts), Facing::Up)];
+
+        let tableau = |column, row| Source::Tableau { column, row };
+        let expected = vec![
+            Action::Draw,
+            Action::QuickMove(tableau(0, 0)),
+            Action::Move(Source::Waste, Destination::Foundation(1)),
+            Action::Move(Source::Waste, Destination::Foundation(2)),
+            Action::Move(Source::Waste, Destination::Foundation(3)),
+            Action::Move(Source::Foundation(0), Destination::Tableau(3)),
+            Action::Move(tableau(2, 0), Destination::Tableau(4)),
+            Action::Move(tableau(2, 0), Destination::Tableau(5)),
+            Action::Move(tableau(2, 0), Destination::Tableau(6)),
+        ];
+        let moves = game.legal_moves();
+        assert_eq!(expected.len(), moves.len(), "{:?}", moves);
+        for action in &expected {
+            assert!(moves.contains(action), "{} missing from {:?}", action, moves);
+        }
+        for action in &moves {
+            assert!(game.clone().apply_action(action).is_ok(), "{}", action);
+        }
+    }
+
+    #[test]
+    fn test_dead_end() {
+        use Rank::*;
+        use Suit::*;
+        let mut game = empty_game(Rules::default());
+        game.foundation[0] = vec![card(Ace, Hearts)];
+        game.tableau[0] = vec![(card(N9, Spades), Facing::Down), (card(N5, Clubs), Facing::Up)];
+        game.tableau[1] = vec![(card(King, Clubs), Facing::Up)];
+        game.stock = Stock::new(vec![
+            card(N7, Clubs), card(N2, Hearts), card(Jack, Hearts), card(N8, Spades),
+        ], 3);
+        // The 2♥ comes up right away, but after that it's a dead end.
+        assert!(!game.is_dead_end());
+        game.apply_action(&Action::Draw).unwrap();
+        assert!(!game.is_dead_end());
+        game.apply_action(&Action::QuickMove(Source::Waste)).unwrap();
+        assert!(game.is_dead_end());
+
+        // The Queen could go on the King, but it never comes up when drawing three...
+        game.stock = Stock::new(vec![card(N7, Clubs), card(Queen, Hearts), card(N8, Spades)], 3);
+        assert!(game.is_dead_end());
+
+        // ...but it does when drawing one.
+        game.stock = Stock::new(vec![card(N7, Clubs), card(Queen, Hearts), card(N8, Spades)], 1);
+        assert!(!game.is_dead_end());
+    }
+
+    #[test]
+    fn test_position_key() {
+        use Rank::*;
+        use Suit::*;
+        let mut game = empty_game(Rules::default());
+        game.tableau[0] = vec![(card(N9, Spades), Facing::Down), (card(N5, Clubs), Facing::Up)];
+        game.tableau[1] = vec![(card(King, Clubs), Facing::Up)];
+        let key = game.position_key();
+
+        // Swapping columns, or changing the score, makes no difference...
+        let mut other = game.clone();
+        other.tableau.swap(0, 1);
+        other.score = 50;
+        assert_eq!(key, other.position_key());
+
+        // ...but turning a card over does, as does moving the same card somewhere else.
+        other.tableau[1][0].1 = Facing::Up;
+        assert_ne!(key, other.position_key());
+        let mut other = game.clone();
+        other.tableau[0].pop();
+        other.tableau[2].push((card(N5, Clubs), Facing::Up));
+        assert_ne!(key, other.position_key());
+    }
+
+    #[test]
+    fn test_dead_end_shuffling() {
+        use Rank::*;
+        use Suit::*;
+        let mut game = empty_game(Rules::default());
+        game.foundation[0] = Rank::all()[.. 5].iter().map(|&rank| card(rank, Spades)).collect();
+        game.tableau[0] = vec![(card(N10, Spades), Facing::Up), (card(N6, Hearts), Facing::Up)];
+        game.tableau[1] = vec![(card(N8, Diamonds), Facing::Up), (card(N7, Clubs), Facing::Up)];
+        game.tableau[2] = vec![(card(N8, Hearts), Facing::Up)];
+        game.stock = Stock::new(
+            vec![card(N9, Clubs), card(Jack, Diamonds), card(N3, Diamonds)], 3);
+        // The 5♠ can come down onto the 6♥, and the 7♣ can go back and forth between the eights,
+        // but none of that gets anywhere.
+        assert!(game.legal_moves().contains(
+            &Action::Move(Source::Foundation(0), Destination::Tableau(0))));
+        assert!(game.is_dead_end());
+
+        // Once there's a 4♥ to put on the 5♠, pulling it down is worth it.
+        game.stock = Stock::new(vec![card(N9, Clubs), card(N4, Hearts)], 1);
+        assert!(!game.is_dead_end());
+    }
+
+    #[test]
+    fn test_status() {
+        use Rank::*;
+        use Suit::*;
+        let mut game = empty_game(Rules { pass_limit: Some(1), ..Rules::default() });
+        assert_eq!(GameStatus::NoMovesLeft, game.status());
+
+        game.stock = Stock::new(vec![card(N2, Spades)], 3);
+        assert_eq!(GameStatus::InProgress, game.status());
+        game.apply_action(&Action::Draw).unwrap();
+        assert_eq!(GameStatus::NoMovesLeft, game.status());
+
+        game.tableau[3].push((card(N3, Hearts), Facing::Up));
+        assert_eq!(GameStatus::InProgress, game.status());
+
+        for (i, &suit) in Suit::all().iter().enumerate() {
+            game.foundation[i] = Rank::all().iter().map(|&rank| card(rank, suit)).collect();
+        }
+        assert_eq!(GameStatus::Won, game.status());
+    }
+}
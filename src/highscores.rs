@@ -0,0 +1,205 @@
+use crate::error::Error;
+use crate::rules::{self, Rules};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How many scores are kept for each set of rules.
+pub const TABLE_SIZE: usize = 10;
+
+/// One winning game in a high score table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub score: i32,
+    pub game_number: u64,
+    pub duration: Duration,
+}
+
+/// The best scores for each set of rules, since scores from different rules can't be compared.
+#[derive(Debug, Default)]
+pub struct HighScores {
+    tables: Vec<(Rules, Vec<Entry>)>,
+}
+
+impl HighScores {
+    fn path(dir: &Path) -> PathBuf {
+        dir.join("highscores")
+    }
+
+    /// Load the high scores from a data directory. A missing file means there are none yet.
+    pub fn load(dir: &Path) -> Result<Self, Error> {
+        let file = match File::open(Self::path(dir)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        Self::read(BufReader::new(file))
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        // Write the whole table out first, so a failure can't leave it half written.
+        let path = Self::path(dir);
+        let tmp = path.with_extension("new");
+        self.write(&mut File::create(&tmp)?)?;
+        fs::rename(tmp, path)
+    }
+
+    /// The high scores for a set of rules, best first.
+    pub fn table(&self, rules: &Rules) -> &[Entry] {
+        self.tables.iter()
+            .find(|(r, _)| r == rules)
+            .map(|(_, entries)| &entries[..])
+            .unwrap_or(&[])
+    }
+
+    /// Whether a score is good enough to make it into the table for a set of rules.
+    pub fn qualifies(&self, rules: &Rules, score: i32) -> bool {
+        let table = self.table(rules);
+        table.len() < TABLE_SIZE || table.iter().any(|entry| score > entry.score)
+    }
+
+    /// Add a score to the table for a set of rules, returning its place in the table (counting
+    /// from zero), or None if it didn't make it in. A score that ties one already in the table
+    /// goes below it.
+    pub fn add(&mut self, rules: Rules, entry: Entry) -> Option<usize> {
+        let idx = match self.tables.iter().position(|(r, _)| *r == rules) {
+            Some(idx) => idx,
+            None => {
+                self.tables.push((rules, vec![]));
+                self.tables.len() - 1
+            }
+        };
+        let table = &mut self.tables[idx].1;
+        let place = table.iter()
+            .position(|other| entry.score > other.score)
+            .unwrap_or(table.len());
+        if place >= TABLE_SIZE {
+            return None;
+        }
+        table.insert(place, entry);
+        table.truncate(TABLE_SIZE);
+        Some(place)
+    }
+
+    /// The high score table for a set of rules, as text to show the player.
+    pub fn format_table(&self, rules: &Rules) -> String {
+        let mut text = format!("High scores: draw {}, {} passes, {} scoring\n\n",
+            rules.draw_count, rules::pass_limit_str(rules.pass_limit), rules.scoring);
+        let table = self.table(rules);
+        if table.is_empty() {
+            text += "Nobody has won a game with these rules yet.";
+        }
+        for (i, entry) in table.iter().enumerate() {
+            let secs = entry.duration.as_secs();
+            writeln!(text, "{:>2}. {:<16} {:>8}  game #{:<10} {}:{:02}",
+                i + 1, entry.name, rules.scoring.format_score(entry.score), entry.game_number,
+                secs / 60, secs % 60).unwrap();
+        }
+        text
+    }
+
+    /// Each table is written as the rules headers used in log files, followed by a line per entry
+    /// with the score, duration in seconds, game number, and name, separated by spaces.
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        for (rules, entries) in &self.tables {
+            rules.write_log_header(w)?;
+            for entry in entries {
+                writeln!(w, "{} {} {} {}", entry.score, entry.duration.as_secs(),
+                    entry.game_number, entry.name)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(r: impl BufRead) -> Result<Self, Error> {
+        let mut scores = Self::default();
+        let mut rules = Rules::default();
+        let mut entries = vec![];
+        for (i, line) in r.lines().enumerate() {
+            let line = line?;
+            let bad_line = |e: Error| e.at_line("high scores", i + 1);
+            if line.starts_with('#') {
+                if !entries.is_empty() {
+                    // Headers after entries begin the next table.
+                    scores.tables.push((rules, entries));
+                    rules = Rules::default();
+                    entries = vec![];
+                }
+                rules.read_log_header(&line).map_err(|e| bad_line(e.into()))?;
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut fields = line.splitn(4, ' ');
+            let mut field = |what| fields.next().ok_or_else(|| bad_line(Error::MissingField(what)));
+            entries.push(Entry {
+                score: field("score")?.parse().map_err(|e| bad_line(Error::Number(e)))?,
+                duration: Duration::from_secs(field("duration")?.parse()
+                    .map_err(|e| bad_line(Error::Number(e)))?),
+                game_number: field("game number")?.parse()
+                    .map_err(|e| bad_line(Error::Number(e)))?,
+                name: field("name")?.to_owned(),
+            });
+        }
+        if !entries.is_empty() {
+            scores.tables.push((rules, entries));
+        }
+        Ok(scores)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scoring::ScoringMode;
+
+    fn entry(name: &str, score: i32) -> Entry {
+        Entry {
+            name: name.to_owned(),
+            score,
+            game_number: 1234,
+            duration: Duration::from_secs(200),
+        }
+    }
+
+    #[test]
+    fn test_add() {
+        let rules = Rules::default();
+        let mut scores = HighScores::default();
+        for i in 0 .. TABLE_SIZE as i32 {
+            assert_eq!(Some(i as usize), scores.add(rules, entry("old", 100 - i)));
+        }
+        assert!(!scores.qualifies(&rules, 91));
+        assert_eq!(None, scores.add(rules, entry("low", 91)));
+        assert!(scores.qualifies(&rules, 95));
+        assert_eq!(Some(6), scores.add(rules, entry("new", 95)));
+        let table = scores.table(&rules);
+        assert_eq!(TABLE_SIZE, table.len());
+        assert_eq!("old", table[5].name);
+        assert_eq!("new", table[6].name);
+        assert_eq!(92, table[9].score);
+
+        // Other rules have their own table.
+        let vegas = Rules { scoring: ScoringMode::Vegas, ..rules };
+        assert!(scores.table(&vegas).is_empty());
+        assert!(scores.qualifies(&vegas, -52));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut scores = HighScores::default();
+        let draw_one = Rules { draw_count: 1, pass_limit: Some(3), ..Rules::default() };
+        scores.add(Rules::default(), entry("Ada Lovelace", 120));
+        scores.add(Rules::default(), entry("Bob", 80));
+        scores.add(draw_one, entry("Carol", 300));
+        let mut buf = vec![];
+        scores.write(&mut buf).unwrap();
+        let loaded = HighScores::read(&buf[..]).unwrap();
+        assert_eq!(scores.table(&Rules::default()), loaded.table(&Rules::default()));
+        assert_eq!(scores.table(&draw_one), loaded.table(&draw_one));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::action::{Action, Destination, Source};
+use crate::game_state::{Facing, GameState, Rank};
+use std::cmp::Reverse;
+
+/// Pick a move to suggest to the player, or None if there's nothing worth doing.
+pub fn hint(state: &GameState) -> Option<Action> {
+    state.legal_moves()
+        .into_iter()
+        .filter_map(|action| rank_move(state, &action).map(|rank| (rank, action)))
+        .min_by_key(|(rank, _)| Reverse(*rank))
+        .map(|(_, action)| action)
+}
+
+/// How good a move looks, or None if it's pointless. Flipping cards over and exposing face-down
+/// cards come first, then plays to the foundation, then plays from the waste. Moves which empty a
+/// column are discouraged unless there's a King ready to fill it.
+pub(crate) fn rank_move(state: &GameState, action: &Action) -> Option<i32> {
+    let (src, dest) = match action {
+        Action::QuickMove(_) => return Some(100), // only flips are listed as quick moves
+        Action::Draw => return Some(5),
+        Action::Move(src, dest) => (src, dest),
+        Action::Quit | Action::Help => return None,
+    };
+
+    let mut rank = match (src, dest) {
+        (Source::Foundation(_), _) => return Some(-1),
+        (_, Destination::Foundation(_)) => 50,
+        (Source::Waste, Destination::Tableau(_)) => 30,
+        (Source::Tableau { .. }, Destination::Tableau(_)) => 10,
+    };
+
+    if let Source::Tableau { column, row } = *src {
+        let cards = state.tableau(column);
+        if row == 0 {
+            if cards[0].0.rank == Rank::King {
+                // Moving a King from one empty space to another does nothing.
+                return None;
+            }
+            if king_available(state, column) {
+                rank += 20;
+            } else {
+                rank -= 15;
+            }
+        } else if let Facing::Down = cards[row - 1].1 {
+            rank += 40;
+        } else if let Destination::Tableau(_) = dest {
+            // Just splitting up a run of cards.
+            rank = 0;
+        }
+    }
+
+    Some(rank)
+}
+
+/// Whether there's a King that could be moved into the given column once it's emptied.
+fn king_available(state: &GameState, emptied_column: usize) -> bool {
+    if state.waste().last().map(|card| card.rank) == Some(Rank::King) {
+        return true;
+    }
+    (0 .. 7)
+        .filter(|&column| column != emptied_column)
+        .any(|column| {
+            state.tableau(column)
+                .iter()
+                .skip(1)
+                .any(|(card, facing)| card.rank == Rank::King && matches!(facing, Facing::Up))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_state::{Card, Suit};
+    use crate::rules::Rules;
+
+    #[test]
+    fn test_hint_exposes_face_down() {
+        // An unshuffled deck deals Kings and Queens into the first few columns, so the red Queen
+        // in column 3 can go on the black King in column 2, exposing a face-down card.
+        let mut deck = vec![];
+        for &rank in Rank::all() {
+            for &suit in Suit::all() {
+                deck.push(Card { suit, rank });
+            }
+        }
+        let state = GameState::new(0, Rules::default(), deck);
+
+        let action = hint(&state).expect("no hint");
+        assert!(state.legal_moves().contains(&action));
+        match action {
+            Action::Move(Source::Tableau { column, row }, Destination::Tableau(_)) => {
+                assert!(matches!(state.tableau(column)[row - 1].1, Facing::Down));
+            }
+            _ => panic!("unexpected hint {}", action),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use crate::action::Action;
+
+/// Everything that has been played in a game, as a tree of moves, so that undoing some moves and
+/// then playing differently doesn't lose the moves that were undone.
+#[derive(Debug, Clone)]
+pub struct History {
+    nodes: Vec<Node>,
+    current: usize,
+    marks: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    parent: Option<usize>,
+    /// The move that led here from the parent.
+    action: Option<Action>,
+    /// The moves played from here, least recently visited first.
+    children: Vec<usize>,
+}
+
+/// One of the ways the game continues from the current position.
+#[derive(Debug)]
+pub struct Branch<'a> {
+    /// The moves played in this branch, following the most recently visited line to its end.
+    pub moves: Vec<&'a Action>,
+    /// Names of the checkpoints along this branch.
+    pub marks: Vec<&'a str>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node { parent: None, action: None, children: vec![] }],
+            current: 0,
+            marks: vec![],
+        }
+    }
+
+    /// The moves made to reach the current position, from the start of the game.
+    pub fn moves(&self) -> Vec<Action> {
+        self.path(self.current)
+    }
+
+    /// Record a move made from the current position, and go to the position after it. If the move
+    /// was made from here before, this goes back into that branch instead of starting a new one.
+    pub fn play(&mut self, action: Action) {
+        let existing = self.nodes[self.current].children.iter()
+            .position(|&child| self.nodes[child].action.as_ref() == Some(&action));
+        let child = match existing {
+            Some(idx) => self.nodes[self.current].children.remove(idx),
+            None => {
+                self.nodes.push(Node {
+                    parent: Some(self.current),
+                    action: Some(action),
+                    children: vec![],
+                });
+                self.nodes.len() - 1
+            }
+        };
+        self.nodes[self.current].children.push(child);
+        self.current = child;
+    }
+
+    /// Go back to the position before the last move, returning that move.
+    pub fn back(&mut self) -> Option<&Action> {
+        let node = self.current;
+        self.current = self.nodes[node].parent?;
+        self.nodes[node].action.as_ref()
+    }
+
+    /// The move that would be made again by redo: the most recently visited move from here.
+    pub fn redo_move(&self) -> Option<&Action> {
+        let &child = self.nodes[self.current].children.last()?;
+        self.nodes[child].action.as_ref()
+    }
+
+    /// Name the current position, so it can be returned to. A name already in use is moved here.
+    pub fn mark(&mut self, name: &str) {
+        self.marks.retain(|(mark, _)| mark != name);
+        self.marks.push((name.to_owned(), self.current));
+    }
+
+    /// How to get from the current position to a named one: how many moves to go back, and which
+    /// moves to make from there.
+    pub fn route(&self, name: &str) -> Option<(usize, Vec<Action>)> {
+        let &(_, target) = self.marks.iter().find(|(mark, _)| mark == name)?;
+        let target_ancestors = self.ancestors(target).collect::<Vec<_>>();
+        let (back, common) = self.ancestors(self.current)
+            .enumerate()
+            .find(|(_, node)| target_ancestors.contains(node))
+            .expect("positions have no common ancestor");
+        let forward = self.path(target).split_off(self.ancestors(common).count() - 1);
+        Some((back, forward))
+    }
+
+    /// The ways the game continues from the current position, least recently visited first.
+    pub fn branches(&self) -> Vec<Branch<'_>> {
+        self.nodes[self.current].children.iter()
+            .map(|&child| {
+                let mut branch = Branch { moves: vec![], marks: vec![] };
+                let mut node = Some(child);
+                while let Some(idx) = node {
+                    branch.moves.extend(&self.nodes[idx].action);
+                    branch.marks.extend(self.marks.iter()
+                        .filter(|&&(_, mark)| mark == idx)
+                        .map(|(name, _)| name.as_str()));
+                    node = self.nodes[idx].children.last().copied();
+                }
+                branch
+            })
+            .collect()
+    }
+
+    /// All the moves in the branch running through a named position: the moves to reach it, and
+    /// then the most recently visited line after it, to its end.
+    pub fn branch_through(&self, name: &str) -> Option<Vec<Action>> {
+        let &(_, mut node) = self.marks.iter().find(|(mark, _)| mark == name)?;
+        while let Some(&child) = self.nodes[node].children.last() {
+            node = child;
+        }
+        Some(self.path(node))
+    }
+
+    /// A node and its ancestors, back to the start of the game.
+    fn ancestors(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(Some(node), move |&idx| self.nodes[idx].parent)
+    }
+
+    /// The moves from the start of the game to a node.
+    fn path(&self, node: usize) -> Vec<Action> {
+        let mut moves = self.ancestors(node)
+            .filter_map(|idx| self.nodes[idx].action.clone())
+            .collect::<Vec<_>>();
+        moves.reverse();
+        moves
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::{Destination, Source};
+
+    fn flip(column: usize) -> Action {
+        Action::QuickMove(Source::Tableau { column, row: 0 })
+    }
+
+    fn to_column(column: usize) -> Action {
+        Action::Move(Source::Waste, Destination::Tableau(column))
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut history = History::new();
+        history.play(Action::Draw);
+        history.play(flip(1));
+        assert_eq!(Some(&flip(1)), history.back());
+        assert_eq!(Some(&flip(1)), history.redo_move());
+        assert_eq!(vec![Action::Draw], history.moves());
+
+        // Playing the undone move again goes back into the same branch.
+        history.play(flip(1));
+        assert_eq!(vec![Action::Draw, flip(1)], history.moves());
+        history.back();
+        history.back();
+        assert_eq!(None, history.back());
+        assert_eq!(1, history.branches().len());
+    }
+
+    #[test]
+    fn test_branches() {
+        let mut history = History::new();
+        history.play(Action::Draw);
+        history.mark("start");
+        history.play(to_column(1));
+        history.play(flip(2));
+        history.mark("one");
+        history.back();
+        history.back();
+        history.play(to_column(3));
+        history.mark("three");
+
+        let (back, forward) = history.route("one").unwrap();
+        assert_eq!(1, back);
+        assert_eq!(vec![to_column(1), flip(2)], forward);
+        assert_eq!((0, vec![]), history.route("three").unwrap());
+        assert_eq!(None, history.route("nowhere"));
+
+        history.back();
+        let branches = history.branches();
+        assert_eq!(2, branches.len());
+        assert_eq!(vec![&to_column(1), &flip(2)], branches[0].moves);
+        assert_eq!(vec!["one"], branches[0].marks);
+        assert_eq!(vec![&to_column(3)], branches[1].moves);
+        assert_eq!(Some(&to_column(3)), history.redo_move());
+
+        assert_eq!(Some(vec![Action::Draw, to_column(1), flip(2)]),
+            history.branch_through("one"));
+        // Through an earlier position, the branch follows the most recently visited line.
+        assert_eq!(Some(vec![Action::Draw, to_column(3)]), history.branch_through("start"));
+    }
+}
@@ -0,0 +1,359 @@
+use std::fmt::{self, Display, Write as _};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// How deeply arrays and objects can be nested, so that a hostile request can't overflow the
+/// stack.
+const MAX_DEPTH: usize = 64;
+
+/// Just enough JSON for the bot protocol: one value per line, read and written without any
+/// formatting whitespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    /// A number, kept as it was written so that large integers aren't rounded.
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    /// An object's members, in the order they were given.
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Build an object from its members.
+    pub fn object(members: Vec<(&str, Value)>) -> Self {
+        Value::Object(members.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+    }
+
+    /// The member of an object with the given name.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+macro_rules! from_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(n: $ty) -> Self {
+                    Value::Number(n.to_string())
+                }
+            }
+        )*
+    }
+}
+
+from_integer!(i32, u32, u64, usize);
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        Value::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => f.write_str(n),
+            Value::String(s) => write_string(f, s),
+            Value::Array(values) => {
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_char(']')
+            }
+            Value::Object(members) => {
+                f.write_char('{')?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i != 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Value {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars().peekable();
+        let value = parse_value(&mut chars, 0)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(value),
+            Some(c) => Err(format!("unexpected {:?} after the end of the value", c)),
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars<'_>>) {
+    while chars.next_if(|c| matches!(c, ' ' | '\t' | '\n' | '\r')).is_some() {}
+}
+
+fn expect(chars: &mut Peekable<Chars<'_>>, expected: char) -> Result<(), String> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => Err(format!("expected {:?}, not {:?}", expected, c)),
+        None => Err(format!("expected {:?}, not the end of the line", expected)),
+    }
+}
+
+/// Parse a value, which is nested inside `depth` arrays and objects.
+fn parse_value(chars: &mut Peekable<Chars<'_>>, depth: usize) -> Result<Value, String> {
+    if depth > MAX_DEPTH {
+        return Err("arrays and objects are nested too deeply".to_owned());
+    }
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('"') => parse_string(chars).map(Value::String),
+        Some('[') => {
+            chars.next();
+            let mut values = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Ok(Value::Array(values));
+            }
+            loop {
+                values.push(parse_value(chars, depth + 1)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => (),
+                    Some(']') => return Ok(Value::Array(values)),
+                    _ => return Err("expected ',' or ']' in array".to_owned()),
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            let mut members = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Ok(Value::Object(members));
+            }
+            loop {
+                skip_whitespace(chars);
+                let name = parse_string(chars)?;
+                expect(chars, ':')?;
+                members.push((name, parse_value(chars, depth + 1)?));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => (),
+                    Some('}') => return Ok(Value::Object(members)),
+                    _ => return Err("expected ',' or '}' in object".to_owned()),
+                }
+            }
+        }
+        Some(c) if *c == '-' || c.is_ascii_digit() => parse_number(chars).map(Value::Number),
+        Some(c) if c.is_ascii_alphabetic() => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "null" => Ok(Value::Null),
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => Err(format!("unexpected {:?}", word)),
+            }
+        }
+        Some(c) => Err(format!("unexpected {:?}", c)),
+        None => Err("expected a value, not the end of the line".to_owned()),
+    }
+}
+
+/// Parse a number, which is an optional minus sign, then either 0 or digits not starting with 0,
+/// then optionally a fraction and an exponent, each with at least one digit.
+fn parse_number(chars: &mut Peekable<Chars<'_>>) -> Result<String, String> {
+    fn digits(chars: &mut Peekable<Chars<'_>>, n: &mut String) -> usize {
+        let mut count = 0;
+        while let Some(c) = chars.next_if(char::is_ascii_digit) {
+            n.push(c);
+            count += 1;
+        }
+        count
+    }
+
+    let mut n = String::new();
+    n.extend(chars.next_if_eq(&'-'));
+    match chars.next_if(char::is_ascii_digit) {
+        Some('0') => n.push('0'),
+        Some(c) => {
+            n.push(c);
+            digits(chars, &mut n);
+        }
+        None => return Err(format!("bad number {:?}", n)),
+    }
+    if let Some(c) = chars.next_if_eq(&'.') {
+        n.push(c);
+        if digits(chars, &mut n) == 0 {
+            return Err(format!("bad number {:?}", n));
+        }
+    }
+    if let Some(c) = chars.next_if(|&c| c == 'e' || c == 'E') {
+        n.push(c);
+        n.extend(chars.next_if(|&c| c == '+' || c == '-'));
+        if digits(chars, &mut n) == 0 {
+            return Err(format!("bad number {:?}", n));
+        }
+    }
+    Ok(n)
+}
+
+fn parse_string(chars: &mut Peekable<Chars<'_>>) -> Result<String, String> {
+    if chars.next() != Some('"') {
+        return Err("expected a string".to_owned());
+    }
+    let mut s = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(s),
+            '\\' => s.push(match chars.next().ok_or("unterminated string")? {
+                '"' => '"',
+                '\\' => '\\',
+                '/' => '/',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let mut code = parse_hex(chars)?;
+                    if (0xd800 .. 0xdc00).contains(&code) {
+                        // A surrogate pair, for a character outside the basic multilingual plane.
+                        if chars.next() != Some('\\') || chars.next() != Some('u') {
+                            return Err("unpaired surrogate in string".to_owned());
+                        }
+                        let low = parse_hex(chars)?;
+                        if !(0xdc00 .. 0xe000).contains(&low) {
+                            return Err("unpaired surrogate in string".to_owned());
+                        }
+                        code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                    }
+                    char::from_u32(code).ok_or("bad unicode escape in string")?
+                }
+                c => return Err(format!("bad escape {:?} in string", c)),
+            }),
+            c => s.push(c),
+        }
+    }
+}
+
+/// Parse the four hex digits of a unicode escape.
+fn parse_hex(chars: &mut Peekable<Chars<'_>>) -> Result<u32, String> {
+    let digits = chars.take(4).collect::<String>();
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("bad unicode escape {:?}", digits));
+    }
+    Ok(u32::from_str_radix(&digits, 16).expect("hex digits"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = r#"{"a":[1,-2.5e3,true,null,0,-0.5,1E+2,3e-0],"b":"x\"y\\z\n","c":{},"d":[]}"#;
+        let value = text.parse::<Value>().unwrap();
+        assert_eq!(text, value.to_string());
+        assert_eq!(Some("x\"y\\z\n"), value.get("b").and_then(Value::as_str));
+        assert_eq!(None, value.get("e"));
+
+        let spaced = " { \"a\" : [ 1 , 2 ] , \"b\":\"\\u00e9\\ud83c\\udca1\" } ";
+        let value = spaced.parse::<Value>().unwrap();
+        assert_eq!(Some(&Value::from(vec![1u32, 2])), value.get("a"));
+        assert_eq!(Some("é🂡"), value.get("b").and_then(Value::as_str));
+    }
+
+    #[test]
+    fn test_errors() {
+        for text in &["", "{", "[1,]", "{\"a\" 1}", "\"abc", "nope", "1 2", "{1:2}", "\"\\q\""] {
+            assert!(text.parse::<Value>().is_err(), "{}", text);
+        }
+        // Numbers that aren't JSON numbers, though they'd parse as floats.
+        for text in &["01", "1.", "-.5", "1e5.", ".5", "-", "1e", "1e+", "+1", "[01]", "00"] {
+            assert!(text.parse::<Value>().is_err(), "{}", text);
+        }
+        // Surrogates that don't pair up, and escapes that aren't four hex digits.
+        let escapes = [
+            r#""\ud83c""#, r#""\ud83c\u0041""#, r#""\udca1""#, r#""\u+041""#, r#""\u00e""#,
+        ];
+        for text in &escapes {
+            assert!(text.parse::<Value>().is_err(), "{}", text);
+        }
+
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(nested(MAX_DEPTH + 1).parse::<Value>().is_ok());
+        assert!(nested(MAX_DEPTH + 2).parse::<Value>().is_err());
+        assert!(nested(100_000).parse::<Value>().is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! The rules and game state of Klondike solitaire, as played by klon.
+//!
+//! Games are numbered: `deal` turns a game number into the same shuffled deck every time, so a
+//! game can be replayed from its number and a list of moves.
+//!
+//! The stable interface is the engine: the `action`, `error`, `game_state`, `rules`, and
+//! `scoring` modules, the types re-exported here, and `deal`. The other public modules are the
+//! parts of the klon program itself, like its sessions, frontends, and files; they're only public
+//! so the program can use them, are hidden from the documentation, and may change in any release.
+
+pub mod action;
+pub mod error;
+pub mod game_state;
+pub mod rules;
+pub mod scoring;
+
+#[doc(hidden)]
+pub mod frontend;
+#[doc(hidden)]
+pub mod game;
+#[doc(hidden)]
+pub mod movelog;
+#[doc(hidden)]
+pub mod save;
+#[doc(hidden)]
+pub mod solver;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod storage;
+#[doc(hidden)]
+pub mod viewer;
+
+pub(crate) mod highscores;
+pub(crate) mod hint;
+pub(crate) mod history;
+
+pub use crate::action::{Action, Destination, Source};
+pub use crate::error::Error;
+pub use crate::game_state::{Card, Facing, GameState, GameStatus, Rank, Stock, Suit, UndoRecord};
+pub use crate::rules::Rules;
+pub use crate::scoring::ScoringMode;
+
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+/// Shuffle a deck for a numbered game and deal it out.
+pub fn deal(game_number: u64, rules: Rules) -> GameState {
+    let mut deck = vec![];
+    for &rank in Rank::all() {
+        for &suit in Suit::all() {
+            let card = Card { suit, rank };
+            deck.push(card);
+        }
+    }
+
+    // Randomize the deck in a repeatable way by seeding a RNG with the given number and using that
+    // to do swaps of cards in the deck.
+    // The number of permutations of a 52-card deck is 52!, which is a 226-bit number, and we're
+    // only using a 64-bit seed, and not doing this n a meticulous way, so obviously this can't
+    // generate all possible decks, but it's proooooobably good enough.
+    let mut rand = <Pcg32 as SeedableRng>::seed_from_u64(game_number);
+    for i in 0 .. deck.len() {
+        let j = rand.gen_range(i .. deck.len());
+        deck.swap(i, j);
+    }
+
+    GameState::new(game_number, rules, deck)
+}
@@ -1,195 +1,327 @@
-mod action;
-mod game_state;
+mod json;
+mod plain;
+mod protocol;
 mod ui;
 
-use crate::action::Action;
-use crate::game_state::{Card, GameState, Rank, Suit};
+use crate::plain::PlainUI;
 use crate::ui::CursesUI;
-use getrandom::getrandom;
-use rand::{Rng, SeedableRng};
-use rand_pcg::Pcg32;
+use klon::frontend::Frontend;
+use klon::game::{random_game_number, Game};
+use klon::movelog::{self, LogHeader, Replay};
+use klon::solver::{self, Verdict};
+use klon::stats::{self, Summary};
+use klon::storage;
+use klon::viewer::Viewer;
+use klon::{deal, Error, GameStatus, Rules, ScoringMode};
 use std::env::args;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use std::io;
 use std::process::exit;
 
-struct Game {
-    state: GameState,
-    undo: Vec<GameState>,
-    moves: Vec<Action>,
-    ui: CursesUI,
-    input_file: Option<BufReader<File>>,
+/// How many positions the solver looks at before giving up.
+const SOLVER_NODE_LIMIT: usize = 500_000;
+
+fn usage() -> ! {
+    eprintln!("{} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    eprintln!("usage: {} [--draw 1|3] [--passes 1|3|unlimited] [--scoring simple|standard|vegas] \
+        [--autocomplete] [--plain] [<game number>]", args().next().unwrap());
+    eprintln!("       {} [<rule options>] --solve <game number>", args().next().unwrap());
+    eprintln!("       {} [--autocomplete] [--plain] --replay <log file>", args().next().unwrap());
+    eprintln!("       {} [--plain] --view <log file>", args().next().unwrap());
+    eprintln!("       {} [<rule options>] --protocol json [<game number>]",
+        args().next().unwrap());
+    eprintln!("       {} --stats", args().next().unwrap());
+    eprintln!("       {} verify <log file>...", args().next().unwrap());
+    exit(1);
+}
+
+/// Start up the frontend to play with: plain text, or curses.
+fn new_frontend(plain: bool) -> Box<dyn Frontend> {
+    if plain {
+        Box::new(PlainUI::new())
+    } else {
+        Box::new(CursesUI::new())
+    }
+}
+
+/// Watch the game in a log file in the replay viewer, without playing.
+fn view_log(path: &str, plain: bool) {
+    let (mut viewer, warnings) = Viewer::open(path).unwrap_or_else(|e| {
+        eprintln!("unable to view {}: {}", path, e);
+        exit(2);
+    });
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+    new_frontend(plain).view(&mut viewer);
+}
+
+/// Print statistics about all the games played.
+fn print_stats() {
+    match storage::data_dir().map_err(Error::from).and_then(|dir| stats::load(&dir)) {
+        Ok(results) => println!("{}", Summary::new(&results)),
+        Err(e) => {
+            eprintln!("unable to load statistics: {}", e);
+            exit(2);
+        }
+    }
 }
 
-impl Game {
-    pub fn new(game_number: u64) -> Self {
-        let mut deck = vec![];
-        for &rank in Rank::all() {
-            for &suit in Suit::all() {
-                let card = Card { suit, rank };
-                deck.push(card);
+/// Check that the moves in a log file can all be made, returning a description of how the game
+/// ends up. Logs don't say how long the game took, so the score doesn't include any time bonus.
+fn verify_log(path: &str) -> Result<String, String> {
+    let (replay, header, warnings) = Replay::open(path).map_err(|e| e.to_string())?;
+    let mut state = deal(replay.game_number, header.rules);
+    for (line, action) in replay.read_moves().map_err(|e| e.to_string())? {
+        state.apply_action(&action)
+            .map_err(|e| format!("illegal move {} at log line {}: {}", action, line, e))?;
+    }
+    let status = state.status();
+    if let Some(outcome) = header.outcome.filter(|&outcome| outcome != status) {
+        return Err(format!("log says the game was {}, but it is {}",
+            movelog::outcome_str(outcome), movelog::outcome_str(status)));
+    }
+    let mut result = format!("game #{} {}, score {}", state.game_number(),
+        movelog::outcome_str(status), state.rules().scoring.format_score(state.score()));
+    for warning in warnings {
+        result += &format!(" (warning: {})", warning);
+    }
+    Ok(result)
+}
+
+/// Check a batch of log files without playing them, printing how each one turns out. Exits with
+/// an error status if any of them can't be replayed.
+fn verify_logs(paths: &[String]) -> ! {
+    let mut failed = false;
+    for path in paths {
+        match verify_log(path) {
+            Ok(result) => println!("{}: {}", path, result),
+            Err(e) => {
+                println!("{}: FAILED: {}", path, e);
+                failed = true;
             }
         }
+    }
+    exit(if failed { 1 } else { 0 });
+}
 
-        // Randomize the deck in a repeatable way by seeding a RNG with the given number and using that
-        // to do swaps of cards in the deck.
-        // The number of permutations of a 52-card deck is 52!, which is a 226-bit number, and we're
-        // only using a 64-bit seed, and not doing this n a meticulous way, so obviously this can't
-        // generate all possible decks, but it's proooooobably good enough.
-        let mut rand = <Pcg32 as SeedableRng>::seed_from_u64(game_number);
-        for i in 0 .. deck.len() {
-            let j = rand.gen_range(i .. deck.len());
-            deck.swap(i, j);
+/// Find out whether a game can be won, printing a log of the moves to win it if so.
+fn solve_game(game_number: u64, rules: Rules) {
+    let state = deal(game_number, rules);
+    match solver::solve(&state, SOLVER_NODE_LIMIT) {
+        Verdict::Winnable(moves) => {
+            eprintln!("game #{} is winnable in {} moves", game_number, moves.len());
+            let stdout = std::io::stdout();
+            let header = LogHeader::new(game_number, rules, GameStatus::Won);
+            if let Err(e) = movelog::write_log(&mut stdout.lock(), &header, &moves) {
+                eprintln!("write error: {}", e);
+                exit(2);
+            }
         }
+        Verdict::Unwinnable => eprintln!("game #{} is unwinnable", game_number),
+        Verdict::Unknown => eprintln!("game #{} is unknown: gave up after looking at {} positions",
+            game_number, SOLVER_NODE_LIMIT),
+    }
+}
 
-        let state = GameState::new(game_number, deck);
-        let ui = CursesUI::new();
+/// Notices when the terminal goes away, so the game in progress can be saved.
+#[cfg(unix)]
+mod hangup {
+    use std::sync::atomic::{AtomicBool, Ordering};
 
-        Self {
-            state,
-            undo: vec![],
-            moves: vec![],
-            ui,
-            input_file: None,
+    static HANGUP: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn handler(_signal: libc::c_int) {
+        HANGUP.store(true, Ordering::SeqCst);
+    }
+
+    pub fn install() {
+        // Without SA_RESTART, the signal interrupts curses waiting for input, which ends the game
+        // loop the same way as reaching the end of the input does.
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGHUP, &action, std::ptr::null_mut());
         }
     }
 
-    fn get_input_text(&mut self) -> Result<Option<String>, String> {
-        loop {
-            let input = match self.input_file.as_mut() {
-                Some(file) => {
-                    let mut line = String::new();
-                    file.read_line(&mut line)
-                        .map_err(|e| e.to_string())?;
-                    if line.is_empty() {
-                        // EOF
-                        self.input_file = None;
-                        continue;
+    pub fn happened() -> bool {
+        HANGUP.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(not(unix))]
+mod hangup {
+    pub fn install() {}
+
+    pub fn happened() -> bool {
+        false
+    }
+}
+
+fn main() {
+    let mut rules = Rules::default();
+    let mut seed = None;
+    let mut autocomplete = false;
+    let mut solve = false;
+    let mut replay = None;
+    let mut view = None;
+    let mut plain = false;
+    let mut protocol = None;
+    let mut args = args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("verify") {
+        let paths = args.skip(1).collect::<Vec<_>>();
+        if paths.is_empty() {
+            usage();
+        }
+        verify_logs(&paths);
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" | "-V" | "--version" => usage(),
+            "--draw" => {
+                let n = args.next().unwrap_or_else(|| usage());
+                rules.draw_count = match Rules::parse_draw_count(&n) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        exit(2);
                     }
-                    if line.starts_with('#') {
-                        continue;
+                };
+            }
+            "--passes" => {
+                let n = args.next().unwrap_or_else(|| usage());
+                rules.pass_limit = match Rules::parse_pass_limit(&n) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        exit(2);
                     }
-                    Some(line)
-                }
-                None => self.ui.get_input(),
-            };
-
-            if let Some(ref input) = input {
-                let lc = input.trim().to_ascii_lowercase();
-                if lc.starts_with("load ") {
-                    let f = File::open(&input.trim()[5..])
-                        .map_err(|e| e.to_string())?;
-                    self.input_file = Some(BufReader::new(f));
-                    continue;
-                }
-                if lc.starts_with("log ") {
-                    let mut f = File::create(&input.trim()[4..])
-                        .map_err(|e| e.to_string())?;
-                    writeln!(f, "# game {}", self.state.game_number()).map_err(|_| "write error")?;
-                    for action in &self.moves {
-                        writeln!(f, "{}", action).map_err(|_| "write error")?;
+                };
+            }
+            "--autocomplete" => autocomplete = true,
+            "--plain" => plain = true,
+            "--protocol" => protocol = Some(args.next().unwrap_or_else(|| usage())),
+            "--solve" => solve = true,
+            "--replay" => replay = Some(args.next().unwrap_or_else(|| usage())),
+            "--view" => view = Some(args.next().unwrap_or_else(|| usage())),
+            "--stats" => {
+                print_stats();
+                return;
+            }
+            "--scoring" => {
+                let mode = args.next().unwrap_or_else(|| usage());
+                rules.scoring = match mode.parse() {
+                    Ok(mode) => mode,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        exit(2);
                     }
-                    self.ui.write("log file written");
-                    continue;
-                }
-                if lc == "undo" {
-                    if let Some(state) = self.undo.pop() {
-                        self.moves.pop();
-                        self.state = state;
-                        self.ui.render(&self.state);
-                    } else {
-                        self.ui.write("no moves to undo");
+                };
+            }
+            n if seed.is_none() => {
+                match n.parse::<u64>() {
+                    Ok(n) => seed = Some(n),
+                    Err(e) => {
+                        eprintln!("invalid game number: {}", e);
+                        exit(2);
                     }
-                    continue;
                 }
             }
-
-            break Ok(input);
+            _ => usage(),
         }
     }
 
-    fn main_loop(&mut self) {
-        loop {
-            self.ui.render(&self.state);
-
-            let input = match self.get_input_text() {
-                Err(e) => {
-                    self.ui.write(&e);
-                    continue;
-                }
-                Ok(Some(input)) => input,
-                Ok(None) => return,
-            };
-
-            let action = match input.trim().parse::<Action>() {
-                Ok(action) => action,
-                Err(e) => {
-                    self.ui.write(e);
-                    continue;
-                }
-            };
-
-            let prev_state = self.state.clone();
-            if let Err(e) = self.state.apply_action(&action) {
-                self.ui.write(e);
-                continue;
-            }
-
-            if let Action::Quit = action {
-                break;
-            }
+    if solve {
+        solve_game(seed.unwrap_or_else(|| usage()), rules);
+        return;
+    }
 
-            if let Action::Help = action {
-                self.ui.halp();
-                continue;
-            }
+    if let Some(protocol) = protocol {
+        if protocol != "json" {
+            eprintln!("the only protocol is json");
+            exit(2);
+        }
+        let stdout = io::stdout();
+        let result = protocol::run(seed.unwrap_or_else(random_game_number), rules,
+            io::stdin().lock(), &mut stdout.lock());
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            exit(2);
+        }
+        return;
+    }
 
-            self.undo.push(prev_state);
-            self.moves.push(action.clone());
+    if let Some(path) = view {
+        view_log(&path, plain);
+        return;
+    }
 
-            if (0..4)
-                .all(|i| self.state.foundation(i)
-                    .map(|card| card.rank)
-                        == Some(Rank::King))
-            {
-                self.ui.write("YOU'RE WINNER !"); // lol
+    let mut game = if let Some(path) = replay {
+        // The log says which game to play, and with which rules.
+        let (replay, header, warnings) = Replay::open(&path).unwrap_or_else(|e| {
+            eprintln!("unable to replay {}: {}", path, e);
+            exit(2);
+        });
+        let mut game = Game::new(replay.game_number, header.rules, new_frontend(plain));
+        game.start_replay(replay, &warnings);
+        game
+    } else {
+        let mut game = Game::new(seed.unwrap_or_else(random_game_number), rules,
+            new_frontend(plain));
+        if seed.is_none() {
+            game.offer_resume();
+        }
+        game
+    };
+    // A resumed game brings its own rules.
+    let rules = game.rules();
+    if rules.scoring == ScoringMode::Vegas {
+        match storage::data_dir().and_then(|dir| storage::load_bankroll(&dir)) {
+            Ok(bankroll) => game.set_bankroll(Some(bankroll)),
+            Err(e) => {
+                drop(game);
+                eprintln!("unable to load bankroll: {}", e);
+                exit(2);
             }
         }
     }
+    game.set_autocomplete(autocomplete);
+    hangup::install();
+    game.main_loop();
+    let (results, bankroll, suspended) = game.end();
 
-    pub fn end(self) -> GameState {
-        self.state
+    if hangup::happened() {
+        // Nobody is there to read anything, but the bankroll still needs saving.
+        if let Some(bankroll) = bankroll {
+            storage::data_dir().and_then(|dir| storage::save_bankroll(&dir, bankroll)).ok();
+        }
+        return;
     }
-}
 
-fn main() {
-    let seed = match args().nth(1).as_deref() {
-        Some("-h") | Some("--help") | Some("-V") | Some("--version") => {
-            eprintln!("{} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
-            eprintln!("usage: {} [<game number>]", args().next().unwrap());
-            exit(1);
+    for result in &results {
+        if result.won {
+            println!("That was game #{}, and you won it.", result.game_number);
+        } else {
+            println!("That was game #{}.", result.game_number);
         }
-        Some(n) => {
-            match n.parse::<u64>() {
-                Ok(n) => n,
-                Err(e) => {
-                    eprintln!("invalid game number: {}", e);
-                    exit(2);
-                }
-            }
+        let secs = result.duration.as_secs();
+        let played = format!("{} moves and {}:{:02}", result.moves, secs / 60, secs % 60);
+        if result.rules.scoring == ScoringMode::Vegas {
+            println!("You won {} this game, in {}.",
+                result.rules.scoring.format_score(result.score), played);
+        } else {
+            println!("You scored {} points in {}.", result.score, played);
         }
-        None => {
-            let mut bytes = [0u8; 8];
-            getrandom(&mut bytes).expect("unable to get random bytes");
-            u64::from_le_bytes(bytes)
+    }
+    if let Some(bankroll) = bankroll {
+        println!("Your bankroll is now {}.", rules.scoring.format_score(bankroll));
+        if let Err(e) = storage::data_dir().and_then(|dir| storage::save_bankroll(&dir, bankroll)) {
+            eprintln!("unable to save bankroll: {}", e);
         }
-    };
-
-    let mut game = Game::new(seed);
-    game.main_loop();
-    let end_state = game.end();
-
-    println!("That was game #{}.", seed);
-    println!("You scored {} points.", end_state.score());
+    }
+    if let Some(game_number) = suspended {
+        println!("Game #{} has been saved; run klon again to resume it.", game_number);
+    }
     println!("Bye!");
 }
@@ -0,0 +1,271 @@
+use crate::action::Action;
+use crate::error::{Error, ParseError};
+use crate::game_state::GameStatus;
+use crate::rules::Rules;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The version of the log file format written by `write_log`. Version 1 added the `# klon log`
+/// line at the start, and the version, date, and outcome headers. Logs without that line are
+/// taken to be version 0.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The comment lines at the start of a log file, describing the game the moves are for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogHeader {
+    pub format_version: u32,
+    /// The version of klon that wrote the log.
+    pub klon_version: Option<String>,
+    pub game_number: Option<u64>,
+    pub rules: Rules,
+    /// When the log was written, in UTC.
+    pub date: Option<String>,
+    /// How the game stood at the end of the moves in the log.
+    pub outcome: Option<GameStatus>,
+}
+
+impl LogHeader {
+    /// A header for a log being written now, by this version of klon.
+    pub fn new(game_number: u64, rules: Rules, outcome: GameStatus) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            klon_version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+            game_number: Some(game_number),
+            rules,
+            date: Some(format_date(SystemTime::now())),
+            outcome: Some(outcome),
+        }
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "# klon log {}", self.format_version)?;
+        if let Some(ref version) = self.klon_version {
+            writeln!(w, "# version {}", version)?;
+        }
+        if let Some(game_number) = self.game_number {
+            writeln!(w, "# game {}", game_number)?;
+        }
+        self.rules.write_log_header(w)?;
+        if let Some(ref date) = self.date {
+            writeln!(w, "# date {}", date)?;
+        }
+        if let Some(outcome) = self.outcome {
+            writeln!(w, "# outcome {}", outcome_str(outcome))?;
+        }
+        Ok(())
+    }
+
+    /// Read the header from the start of a log file, leaving the reader at the first move.
+    /// Returns the header and how many lines it took up.
+    pub fn read(r: &mut impl BufRead) -> Result<(Self, usize), Error> {
+        let mut header = Self {
+            format_version: 0,
+            klon_version: None,
+            game_number: None,
+            rules: Rules::default(),
+            date: None,
+            outcome: None,
+        };
+        let mut line = String::new();
+        let mut lines = 0;
+        loop {
+            if r.fill_buf()?.first() != Some(&b'#') {
+                break;
+            }
+            lines += 1;
+            line.clear();
+            r.read_line(&mut line)?;
+            let bad_line = |e: Error| e.at_line("log", lines);
+            if let Some(version) = line.strip_prefix("# klon log ") {
+                header.format_version = version.trim().parse()
+                    .map_err(|e| bad_line(Error::Number(e)))?;
+                continue;
+            }
+            match header_fields(&line) {
+                Some(("version", value)) => header.klon_version = Some(value.to_owned()),
+                Some(("game", value)) => {
+                    header.game_number = Some(value.parse()
+                        .map_err(|e| bad_line(Error::Number(e)))?);
+                }
+                Some(("date", value)) => header.date = Some(value.to_owned()),
+                Some(("outcome", value)) => {
+                    header.outcome = Some(parse_outcome(value).map_err(bad_line)?);
+                }
+                _ => header.rules.read_log_header(&line).map_err(|e| bad_line(e.into()))?,
+            }
+        }
+        Ok((header, lines))
+    }
+
+    /// Check that the moves in the log can be replayed, returning the game number they are for.
+    /// Problems which mean the log can't be replayed are errors; anything else that's off is
+    /// returned as a warning.
+    pub fn check(&self) -> Result<(u64, Vec<String>), Error> {
+        if self.format_version > FORMAT_VERSION {
+            return Err(Error::LogTooNew(self.format_version));
+        }
+        let game_number = self.game_number.ok_or(Error::LogWithoutGame)?;
+
+        let mut warnings = vec![];
+        if self.format_version == 0 {
+            warnings.push("log is from an older klon, and may not replay correctly".to_owned());
+        } else if let Some(ref version) = self.klon_version {
+            if version != env!("CARGO_PKG_VERSION") {
+                warnings.push(format!("log was written by klon {}", version));
+            }
+        }
+        Ok((game_number, warnings))
+    }
+}
+
+/// A log file whose moves are being replayed.
+pub struct Replay {
+    reader: BufReader<File>,
+    pub game_number: u64,
+    /// How many lines of the file have been read.
+    line: usize,
+}
+
+impl Replay {
+    /// Open a log file and read its header, returning it along with any warnings about it.
+    pub fn open(path: &str) -> Result<(Self, LogHeader, Vec<String>), Error> {
+        let f = File::open(path)?;
+        let mut reader = BufReader::new(f);
+        let (header, line) = LogHeader::read(&mut reader)?;
+        let (game_number, warnings) = header.check()?;
+        Ok((Self { reader, game_number, line }, header, warnings))
+    }
+
+    /// Read the next line of the log, or None at the end of it.
+    pub fn next_line(&mut self) -> Result<Option<String>, Error> {
+        let mut text = String::new();
+        if self.reader.read_line(&mut text)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        Ok(Some(text))
+    }
+
+    /// Which line of the log was read last.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Read the rest of the moves in the log, along with the line each is on.
+    pub fn read_moves(mut self) -> Result<Vec<(usize, Action)>, Error> {
+        let mut moves = vec![];
+        while let Some(text) = self.next_line()? {
+            if text.starts_with('#') || text.trim().is_empty() {
+                continue;
+            }
+            let action = text.trim().parse()
+                .map_err(|e: ParseError| Error::from(e).at_line("log", self.line))?;
+            moves.push((self.line, action));
+        }
+        Ok(moves)
+    }
+}
+
+/// Write a log of moves made in a game, in the format that the `load` command reads.
+pub fn write_log(w: &mut impl Write, header: &LogHeader, moves: &[Action]) -> io::Result<()> {
+    header.write(w)?;
+    for action in moves {
+        writeln!(w, "{}", action)?;
+    }
+    Ok(())
+}
+
+/// Split a comment line from a log file into a name and value, like `# game 1234`.
+pub fn header_fields(line: &str) -> Option<(&str, &str)> {
+    let mut words = line.strip_prefix('#')?.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some(name), Some(value)) => Some((name, value)),
+        _ => None,
+    }
+}
+
+pub fn outcome_str(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::InProgress => "unfinished",
+        GameStatus::Won => "won",
+        GameStatus::NoMovesLeft => "lost",
+    }
+}
+
+fn parse_outcome(s: &str) -> Result<GameStatus, Error> {
+    match s {
+        "unfinished" => Ok(GameStatus::InProgress),
+        "won" => Ok(GameStatus::Won),
+        "lost" => Ok(GameStatus::NoMovesLeft),
+        _ => Err(Error::BadOutcome),
+    }
+}
+
+/// Format a time as an ISO 8601 date and time in UTC, like `2021-03-04T05:06:07Z`.
+fn format_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Convert days since 1970-01-01 to a date in the proleptic Gregorian calendar, counting in
+    // 400 year eras which start on March 1st, so that leap days come at the end of the year.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scoring::ScoringMode;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!("1970-01-01T00:00:00Z", format_date(UNIX_EPOCH));
+        let date = UNIX_EPOCH + Duration::from_secs(951_782_400 + 3723);
+        assert_eq!("2000-02-29T01:02:03Z", format_date(date));
+        let date = UNIX_EPOCH + Duration::from_secs(1_798_761_599);
+        assert_eq!("2026-12-31T23:59:59Z", format_date(date));
+    }
+
+    #[test]
+    fn test_header() {
+        let rules = Rules { draw_count: 1, pass_limit: Some(3), scoring: ScoringMode::Vegas };
+        let header = LogHeader::new(1234, rules, GameStatus::Won);
+        let mut buf = vec![];
+        write_log(&mut buf, &header, &[Action::Draw]).unwrap();
+        let mut r = &buf[..];
+        assert_eq!((header.clone(), 8), LogHeader::read(&mut r).unwrap());
+        assert_eq!(b"DD\n", r);
+        assert_eq!((1234, vec![]), header.check().unwrap());
+
+        let newer = LogHeader { format_version: FORMAT_VERSION + 1, ..header.clone() };
+        assert!(matches!(newer.check(), Err(Error::LogTooNew(v)) if v == FORMAT_VERSION + 1));
+        let other_version = LogHeader { klon_version: Some("0.1.0".to_owned()), ..header.clone() };
+        assert_eq!(1, other_version.check().unwrap().1.len());
+        let no_game = LogHeader { game_number: None, ..header };
+        assert!(matches!(no_game.check(), Err(Error::LogWithoutGame)));
+    }
+
+    #[test]
+    fn test_old_header() {
+        let mut r = &b"# game 42\nDD\n"[..];
+        let (header, lines) = LogHeader::read(&mut r).unwrap();
+        assert_eq!(1, lines);
+        assert_eq!(0, header.format_version);
+        assert_eq!(Rules::default(), header.rules);
+        assert_eq!(42, header.check().unwrap().0);
+        assert_eq!(1, header.check().unwrap().1.len());
+    }
+}
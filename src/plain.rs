@@ -0,0 +1,210 @@
+use klon::action::Action;
+use klon::frontend::{EndChoice, Frontend, HELP};
+use klon::game_state::{Card, Facing, GameState, GameStatus};
+use klon::viewer::Viewer;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+const VIEWER_KEYS: &str = "enter next, ',' back, a move number to go to it, or q to quit";
+
+/// A frontend that prints the board as ordinary text and reads commands a line at a time, for
+/// terminals curses can't handle, pipes, and screen readers.
+pub struct PlainUI {
+    bankroll: Option<i32>,
+    /// The game as it was last printed, so it isn't printed again when nothing has changed.
+    shown: Option<GameState>,
+}
+
+fn card_str(card: &Card) -> String {
+    format!("{}{}", card.rank, card.suit)
+}
+
+impl PlainUI {
+    pub fn new() -> Self {
+        Self {
+            bankroll: None,
+            shown: None,
+        }
+    }
+
+    /// The board as lines of text, labelled with the positions that moves are typed with.
+    fn board(&self, game: &GameState) -> String {
+        let scoring = game.rules().scoring;
+        let mut text = format!("game #{}, score {}", game.game_number(),
+            scoring.format_score(game.score()));
+        if let Some(bankroll) = self.bankroll {
+            write!(text, ", bank {}", scoring.format_score(bankroll + game.score())).unwrap();
+        }
+
+        let stock_size = game.stock_size().min(game.draw_count());
+        let stock = if stock_size != 0 {
+            format!("draw {}", stock_size)
+        } else if game.waste().is_empty() {
+            "empty".to_owned()
+        } else if game.stock_exhausted() {
+            "done".to_owned()
+        } else {
+            "recycle".to_owned()
+        };
+        write!(text, "\nDD {}, pass {}", stock, game.passes()).unwrap();
+        if let Some(limit) = game.rules().pass_limit {
+            write!(text, "/{}", limit).unwrap();
+        }
+
+        text += "\nW ";
+        if game.waste().is_empty() {
+            text += " empty";
+        }
+        for card in game.waste() {
+            write!(text, " {}", card_str(card)).unwrap();
+        }
+
+        text.push('\n');
+        for i in 0 .. 4 {
+            let card = game.foundation(i).map_or_else(|| "()".to_owned(), card_str);
+            write!(text, "{}0{} {}", if i == 0 { "" } else { "  " }, (b'A' + i as u8) as char,
+                card).unwrap();
+        }
+
+        for i in 0 .. 7 {
+            write!(text, "\n{} ", i + 1).unwrap();
+            if game.tableau(i).is_empty() {
+                text += " empty";
+            }
+            for (j, (card, facing)) in game.tableau(i).iter().enumerate() {
+                let card = match facing {
+                    Facing::Down => "---".to_owned(),
+                    Facing::Up => card_str(card),
+                };
+                write!(text, " {}{} {}", i + 1, (b'A' + j as u8) as char, card).unwrap();
+            }
+        }
+        text
+    }
+}
+
+impl Frontend for PlainUI {
+    fn render(&mut self, game: &GameState) {
+        if self.shown.as_ref() == Some(game) {
+            return;
+        }
+        println!("{}\n", self.board(game));
+        self.shown = Some(game.clone());
+    }
+
+    fn get_input(&mut self) -> Option<String> {
+        self.prompt("your move: ")
+    }
+
+    fn prompt(&mut self, prompt: &str) -> Option<String> {
+        print!("{}", prompt);
+        io::stdout().flush().ok();
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(&['\r', '\n'][..]).to_owned()),
+        }
+    }
+
+    fn confirm(&mut self, question: &str) -> bool {
+        self.prompt(question)
+            .is_some_and(|answer| answer.trim().to_ascii_lowercase().starts_with('y'))
+    }
+
+    fn write(&mut self, text: &str) {
+        println!("{}", text);
+    }
+
+    fn show_text(&mut self, text: &str) {
+        println!("{}\n", text);
+    }
+
+    fn halp(&mut self) {
+        println!("{}\n", HELP);
+    }
+
+    // Nobody is watching the board change, so there's no need to wait.
+    fn pause(&mut self, _ms: i32) {}
+
+    fn end_screen(&mut self, game: &GameState, status: GameStatus) -> EndChoice {
+        let headline = match status {
+            GameStatus::Won => "YOU'RE WINNER !",
+            GameStatus::NoMovesLeft | GameStatus::InProgress => "No moves left.",
+        };
+        println!("{} Final score: {}", headline, game.rules().scoring.format_score(game.score()));
+        loop {
+            let question = "n for a new deal, r to replay this deal, or q to quit: ";
+            let answer = match self.prompt(question) {
+                Some(answer) => answer.trim().to_ascii_lowercase(),
+                None => return EndChoice::Quit,
+            };
+            match answer.as_str() {
+                "n" => break EndChoice::NewDeal,
+                "r" => break EndChoice::Replay,
+                "q" => break EndChoice::Quit,
+                _ => (),
+            }
+        }
+    }
+
+    // The game says what the hint is, and there's nothing to highlight.
+    fn set_hint(&mut self, _hint: Option<Action>) {}
+
+    fn set_bankroll(&mut self, bankroll: Option<i32>) {
+        self.bankroll = bankroll;
+        self.shown = None;
+    }
+
+    fn view(&mut self, viewer: &mut Viewer) {
+        let mut message = None;
+        loop {
+            self.render(viewer.state());
+            println!("{}", viewer.status(false));
+            println!("{}", message.take().unwrap_or(VIEWER_KEYS));
+            let input = match self.prompt("> ") {
+                Some(input) => input.trim().to_ascii_lowercase(),
+                None => break,
+            };
+            match input.as_str() {
+                "" | "." => {
+                    if !viewer.step_forward() {
+                        message = Some("that's the end of the game");
+                    }
+                }
+                "," => {
+                    if !viewer.step_back() {
+                        message = Some("that's the start of the game");
+                    }
+                }
+                "q" => break,
+                n => match n.parse() {
+                    Ok(n) => viewer.jump(n),
+                    Err(_) => message = Some("that's not a move number"),
+                },
+            }
+        }
+        // The game will want to be shown again afterwards.
+        self.shown = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use klon::{deal, Rules};
+
+    #[test]
+    fn test_board() {
+        let mut game = deal(5, Rules::default());
+        game.draw();
+        let board = PlainUI::new().board(&game);
+        let lines = board.lines().collect::<Vec<_>>();
+        assert_eq!(11, lines.len(), "{}", board);
+        assert_eq!("game #5, score 0pts", lines[0]);
+        assert_eq!("DD draw 3, pass 1", lines[1]);
+        assert_eq!("W  4♥ Q♦ 9♦", lines[2]);
+        assert_eq!("0A ()  0B ()  0C ()  0D ()", lines[3]);
+        assert_eq!("1  1A 4♠", lines[4]);
+        assert_eq!("3  3A --- 3B --- 3C 9♥", lines[6]);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::json::Value;
+use klon::game::random_game_number;
+use klon::error::{MoveError, MoveErrorKind};
+use klon::game_state::{Card, Facing, GameState, Suit, UndoRecord};
+use klon::movelog;
+use klon::{deal, Action, Destination, Rules, Source};
+use std::io::{self, BufRead, Write};
+
+/// Plays games with a bot, speaking newline-delimited JSON: each line of input is a request, and
+/// each is answered with a line of output. Every answer that isn't an error is the state of the
+/// game, as the player would see it.
+///
+/// Requests are objects with either an `action`, which is a move written the way it would be
+/// typed, or a `command`: `state` to get the state again, `undo`, `new` to deal a new game (the
+/// one numbered by `game`, if given), or `quit`. The session also ends at the end of the input.
+///
+/// Errors have a short name for what kind of error it was, and a message. Illegal moves also say
+/// the reason the move can't be made, like `same_color`, along with the card, source, and
+/// destination involved, where there are any.
+pub fn run(game_number: u64, rules: Rules, input: impl BufRead, output: &mut impl Write)
+    -> io::Result<()>
+{
+    let mut session = Session {
+        state: deal(game_number, rules),
+        undo: vec![],
+    };
+    writeln!(output, "{}", session.state_json())?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match session.handle(&line) {
+            Ok(Some(reply)) => reply,
+            Ok(None) => break,
+            Err(error) => error,
+        };
+        writeln!(output, "{}", reply)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+struct Session {
+    state: GameState,
+    undo: Vec<UndoRecord>,
+}
+
+/// What went wrong with a request, as the reply to send back.
+type Error = Value;
+
+impl Session {
+    /// Carry out a request, returning the reply, or None to end the session.
+    fn handle(&mut self, line: &str) -> Result<Option<Value>, Error> {
+        let request = line.parse::<Value>().map_err(|e| error_json("bad_json", &e))?;
+        if let Some(action) = request.get("action") {
+            let action = action.as_str()
+                .ok_or_else(|| error_json("bad_request", "action must be a string"))?
+                .parse::<Action>()
+                .map_err(|e| error_json("bad_action", &e.to_string()))?;
+            match action {
+                Action::Quit => return Ok(None),
+                Action::Help => return Err(error_json("bad_action", "help is not a move")),
+                _ => (),
+            }
+            let record = self.state.apply_action(&action).map_err(|e| move_error_json(&e))?;
+            self.undo.push(record);
+            return Ok(Some(self.state_json()));
+        }
+
+        match request.get("command").and_then(Value::as_str) {
+            Some("state") => (),
+            Some("undo") => {
+                let record = self.undo.pop()
+                    .ok_or_else(|| error_json("no_undo", "no moves to undo"))?;
+                self.state.undo_move(&record);
+            }
+            Some("new") => {
+                let game_number = match request.get("game") {
+                    Some(n) => n.as_u64()
+                        .ok_or_else(|| error_json("bad_request", "game must be a game number"))?,
+                    None => random_game_number(),
+                };
+                self.state = deal(game_number, self.state.rules());
+                self.undo.clear();
+            }
+            Some("quit") => return Ok(None),
+            Some(other) => {
+                return Err(error_json("bad_request", &format!("unknown command {:?}", other)));
+            }
+            None => {
+                return Err(error_json("bad_request", "request must have an action or a command"));
+            }
+        }
+        Ok(Some(self.state_json()))
+    }
+
+    fn state_json(&self) -> Value {
+        let state = &self.state;
+        let rules = state.rules();
+        let tableau = (0 .. 7)
+            .map(|i| Value::Array(state.tableau(i).iter()
+                .map(|(card, facing)| match facing {
+                    Facing::Up => Value::object(vec![
+                        ("up", true.into()),
+                        ("card", card_json(card)),
+                    ]),
+                    Facing::Down => Value::object(vec![("up", false.into())]),
+                })
+                .collect()))
+            .collect::<Vec<_>>();
+        Value::object(vec![
+            ("type", "state".into()),
+            ("game", state.game_number().into()),
+            ("rules", Value::object(vec![
+                ("draw", rules.draw_count.into()),
+                ("passes", rules.pass_limit.into()),
+                ("scoring", rules.scoring.to_string().into()),
+            ])),
+            ("status", movelog::outcome_str(state.status()).into()),
+            ("score", state.score().into()),
+            ("stock", state.stock_size().into()),
+            ("passes", state.passes().into()),
+            ("waste", Value::Array(state.waste().iter().map(card_json).collect())),
+            ("foundation", Value::Array((0 .. 4)
+                .map(|i| state.foundation(i).map_or(Value::Null, card_json))
+                .collect())),
+            ("tableau", Value::Array(tableau)),
+            ("legal_moves", state.legal_moves().iter()
+                .map(Action::to_string)
+                .collect::<Vec<_>>()
+                .into()),
+        ])
+    }
+}
+
+fn card_json(card: &Card) -> Value {
+    let suit = match card.suit {
+        Suit::Spades => "spades",
+        Suit::Clubs => "clubs",
+        Suit::Hearts => "hearts",
+        Suit::Diamonds => "diamonds",
+    };
+    Value::object(vec![("rank", u32::from(card.rank.value()).into()), ("suit", suit.into())])
+}
+
+fn error_json(kind: &str, message: &str) -> Value {
+    Value::object(vec![
+        ("type", "error".into()),
+        ("error", kind.into()),
+        ("message", message.into()),
+    ])
+}
+
+fn move_error_json(e: &MoveError) -> Value {
+    Value::object(vec![
+        ("type", "error".into()),
+        ("error", "illegal_move".into()),
+        ("message", e.to_string().into()),
+        ("reason", reason(e.kind).into()),
+        ("card", e.card.as_ref().map_or(Value::Null, card_json)),
+        ("source", e.source.as_ref().map(Source::to_string).into()),
+        ("destination", e.destination.as_ref().map(Destination::to_string).into()),
+    ])
+}
+
+/// The name of a kind of illegal move, as bots see it. These are part of the protocol, so they
+/// mustn't change when the variants are renamed.
+fn reason(kind: MoveErrorKind) -> &'static str {
+    use MoveErrorKind::*;
+    match kind {
+        NoSuchPile => "no_such_pile",
+        NotKing => "not_king",
+        OntoFaceDown => "onto_face_down",
+        SameColor => "same_color",
+        NotOneLower => "not_one_lower",
+        NotAce => "not_ace",
+        WrongSuit => "wrong_suit",
+        NotOneHigher => "not_one_higher",
+        NoPassesLeft => "no_passes_left",
+        StockEmpty => "stock_empty",
+        NotBottomCard => "not_bottom_card",
+        AlreadyOnFoundation => "already_on_foundation",
+        NeedsDestination => "needs_destination",
+        NoFoundationFits => "no_foundation_fits",
+        WasteEmpty => "waste_empty",
+        FoundationEmpty => "foundation_empty",
+        FaceDown => "face_down",
+        NoCard => "no_card",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn replies(input: &str) -> Vec<Value> {
+        let mut output = vec![];
+        run(5, Rules::default(), input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap().lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_session() {
+        let replies = replies(concat!(
+            "{\"action\":\"DD\"}\n",
+            "{\"action\":\"0A\"}\n",
+            "not json\n",
+            "{\"command\":\"undo\"}\n",
+            "{\"command\":\"new\",\"game\":7}\n",
+            "{\"command\":\"quit\"}\n",
+            "{\"command\":\"state\"}\n",
+        ));
+        assert_eq!(6, replies.len());
+
+        let start = &replies[0];
+        assert_eq!(Some("state"), start.get("type").and_then(Value::as_str));
+        assert_eq!(Some(5), start.get("game").and_then(Value::as_u64));
+        assert_eq!(Some(24), start.get("stock").and_then(Value::as_u64));
+        assert_eq!(Some(&Value::Array(vec![])), start.get("waste"));
+        let column = match start.get("tableau") {
+            Some(Value::Array(columns)) => columns[1].to_string(),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(r#"[{"up":false},{"up":true,"card":{"rank":7,"suit":"spades"}}]"#, column);
+        assert!(start.get("legal_moves").unwrap().to_string().contains("\"DD\""));
+
+        assert_eq!(Some(21), replies[1].get("stock").and_then(Value::as_u64));
+        assert_eq!(Some("illegal_move"), replies[2].get("error").and_then(Value::as_str));
+        assert_eq!(Some("foundation is empty"), replies[2].get("message").and_then(Value::as_str));
+        assert_eq!(Some("foundation_empty"), replies[2].get("reason").and_then(Value::as_str));
+        assert_eq!(Some("0A"), replies[2].get("source").and_then(Value::as_str));
+        assert_eq!(Some(&Value::Null), replies[2].get("card"));
+        assert_eq!(Some("bad_json"), replies[3].get("error").and_then(Value::as_str));
+        assert_eq!(Some(24), replies[4].get("stock").and_then(Value::as_u64));
+        assert_eq!(Some(7), replies[5].get("game").and_then(Value::as_u64));
+    }
+
+    #[test]
+    fn test_reasons() {
+        use MoveErrorKind::*;
+        let reasons = [
+            (NoSuchPile, "no_such_pile"),
+            (NotKing, "not_king"),
+            (OntoFaceDown, "onto_face_down"),
+            (SameColor, "same_color"),
+            (NotOneLower, "not_one_lower"),
+            (NotAce, "not_ace"),
+            (WrongSuit, "wrong_suit"),
+            (NotOneHigher, "not_one_higher"),
+            (NoPassesLeft, "no_passes_left"),
+            (StockEmpty, "stock_empty"),
+            (NotBottomCard, "not_bottom_card"),
+            (AlreadyOnFoundation, "already_on_foundation"),
+            (NeedsDestination, "needs_destination"),
+            (NoFoundationFits, "no_foundation_fits"),
+            (WasteEmpty, "waste_empty"),
+            (FoundationEmpty, "foundation_empty"),
+            (FaceDown, "face_down"),
+            (NoCard, "no_card"),
+        ];
+        for &(kind, expected) in &reasons {
+            assert_eq!(expected, reason(kind), "{:?}", kind);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::error::RuleError;
+use crate::movelog;
+use crate::scoring::ScoringMode;
+use std::io::{self, Write};
+
+/// The rule options chosen when a game is started. These affect how the game plays, so they are
+/// written into log files along with the game number.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rules {
+    /// How many cards are turned from the stock to the waste at a time.
+    pub draw_count: usize,
+
+    /// How many times the player may go through the stock, or None for no limit.
+    pub pass_limit: Option<u32>,
+
+    /// How the game is scored.
+    pub scoring: ScoringMode,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            draw_count: 3,
+            pass_limit: None,
+            scoring: ScoringMode::Simple,
+        }
+    }
+}
+
+impl Rules {
+    pub fn parse_draw_count(s: &str) -> Result<usize, RuleError> {
+        match s {
+            "1" => Ok(1),
+            "3" => Ok(3),
+            _ => Err(RuleError::DrawCount),
+        }
+    }
+
+    pub fn parse_pass_limit(s: &str) -> Result<Option<u32>, RuleError> {
+        match s {
+            "unlimited" => Ok(None),
+            "1" => Ok(Some(1)),
+            "3" => Ok(Some(3)),
+            _ => Err(RuleError::PassLimit),
+        }
+    }
+
+    /// Whether the waste may be recycled into the stock, given how many passes through the stock
+    /// have been made so far.
+    pub fn can_recycle(&self, passes: u32) -> bool {
+        match self.pass_limit {
+            Some(limit) => passes < limit,
+            None => true,
+        }
+    }
+
+    pub fn write_log_header(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "# draw {}", self.draw_count)?;
+        writeln!(w, "# passes {}", pass_limit_str(self.pass_limit))?;
+        writeln!(w, "# scoring {}", self.scoring)
+    }
+
+    /// Set the rule described by a comment line from a log file. Lines which don't describe a
+    /// rule are ignored.
+    pub fn read_log_header(&mut self, line: &str) -> Result<(), RuleError> {
+        match movelog::header_fields(line) {
+            Some(("draw", value)) => self.draw_count = Self::parse_draw_count(value)?,
+            Some(("passes", value)) => self.pass_limit = Self::parse_pass_limit(value)?,
+            Some(("scoring", value)) => self.scoring = value.parse()?,
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+pub fn pass_limit_str(pass_limit: Option<u32>) -> String {
+    match pass_limit {
+        Some(limit) => limit.to_string(),
+        None => "unlimited".to_owned(),
+    }
+}
@@ -0,0 +1,157 @@
+use crate::action::Action;
+use crate::error::{Error, ParseError};
+use crate::movelog;
+use crate::rules::Rules;
+use std::fmt::{self, Display};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Something the player did which changed the game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Play(Action),
+    Undo,
+    Redo,
+    Mark(String),
+    Goto(String),
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Play(action) => action.fmt(f),
+            Command::Undo => f.write_str("undo"),
+            Command::Redo => f.write_str("redo"),
+            Command::Mark(name) => write!(f, "mark {}", name),
+            Command::Goto(name) => write!(f, "goto {}", name),
+        }
+    }
+}
+
+impl std::str::FromStr for Command {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "undo" => Ok(Command::Undo),
+            "redo" => Ok(Command::Redo),
+            _ if s.starts_with("mark ") => Ok(Command::Mark(s[5..].to_owned())),
+            _ if s.starts_with("goto ") => Ok(Command::Goto(s[5..].to_owned())),
+            _ => Ok(Command::Play(s.parse()?)),
+        }
+    }
+}
+
+/// A game in progress, saved so that it can be resumed later.
+///
+/// Rather than the state of the game itself, this records everything the player did to it, so
+/// that doing it all again reproduces the game exactly, including its undo history.
+#[derive(Debug, Clone)]
+pub struct SavedGame {
+    pub game_number: u64,
+    pub rules: Rules,
+    pub elapsed: Duration,
+    pub journal: Vec<Command>,
+}
+
+impl SavedGame {
+    fn path(dir: &Path) -> PathBuf {
+        dir.join("saved_game")
+    }
+
+    /// Load the game saved in a data directory, if there is one.
+    pub fn load(dir: &Path) -> Result<Option<Self>, Error> {
+        let file = match File::open(Self::path(dir)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Self::read(BufReader::new(file)).map(Some)
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let mut file = File::create(Self::path(dir))?;
+        self.write(&mut file)
+    }
+
+    /// Delete the saved game, if there is one.
+    pub fn remove(dir: &Path) -> io::Result<()> {
+        match fs::remove_file(Self::path(dir)) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "# game {}", self.game_number)?;
+        self.rules.write_log_header(w)?;
+        writeln!(w, "# elapsed {}", self.elapsed.as_secs())?;
+        for command in &self.journal {
+            writeln!(w, "{}", command)?;
+        }
+        Ok(())
+    }
+
+    fn read(r: impl BufRead) -> Result<Self, Error> {
+        let mut game_number = None;
+        let mut rules = Rules::default();
+        let mut elapsed = Duration::default();
+        let mut journal = vec![];
+        for (i, line) in r.lines().enumerate() {
+            let line = line?;
+            let bad_line = |e: Error| e.at_line("saved game", i + 1);
+            if line.starts_with('#') {
+                match movelog::header_fields(&line) {
+                    Some(("game", value)) => {
+                        game_number = Some(value.parse().map_err(|e| bad_line(Error::Number(e)))?);
+                    }
+                    Some(("elapsed", value)) => {
+                        elapsed = Duration::from_secs(value.parse()
+                            .map_err(|e| bad_line(Error::Number(e)))?);
+                    }
+                    _ => rules.read_log_header(&line).map_err(|e| bad_line(e.into()))?,
+                }
+            } else {
+                journal.push(line.trim().parse().map_err(|e| bad_line(Error::Parse(e)))?);
+            }
+        }
+        Ok(Self {
+            game_number: game_number.ok_or(Error::SaveWithoutGame)?,
+            rules,
+            elapsed,
+            journal,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::Source;
+    use crate::scoring::ScoringMode;
+
+    #[test]
+    fn test_round_trip() {
+        let saved = SavedGame {
+            game_number: 1234,
+            rules: Rules { draw_count: 1, pass_limit: Some(3), scoring: ScoringMode::Vegas },
+            elapsed: Duration::from_secs(95),
+            journal: vec![
+                Command::Play(Action::Draw),
+                Command::Play(Action::QuickMove(Source::Waste)),
+                Command::Mark("here".to_owned()),
+                Command::Undo,
+                Command::Redo,
+                Command::Goto("here".to_owned()),
+            ],
+        };
+        let mut buf = vec![];
+        saved.write(&mut buf).unwrap();
+        let loaded = SavedGame::read(&buf[..]).unwrap();
+        assert_eq!(saved.game_number, loaded.game_number);
+        assert_eq!(saved.rules, loaded.rules);
+        assert_eq!(saved.elapsed, loaded.elapsed);
+        assert_eq!(saved.journal, loaded.journal);
+    }
+}
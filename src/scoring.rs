@@ -0,0 +1,191 @@
+use crate::error::RuleError;
+use crate::rules::Rules;
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+/// Things that happen during a game which may be worth points.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScoreEvent {
+    WasteToTableau,
+    WasteToFoundation,
+    TableauToFoundation,
+    FoundationToTableau,
+    FlipCard,
+    RecycleWaste,
+    Undo,
+}
+
+/// A way of scoring the game.
+pub trait ScoringPolicy: Sync {
+    /// The score a game starts with.
+    fn initial_score(&self) -> i32 {
+        0
+    }
+
+    /// The new score after the given event happens.
+    fn score_event(&self, score: i32, event: ScoreEvent, rules: &Rules) -> i32;
+
+    /// Points awarded for winning the game after the given amount of time.
+    fn time_bonus(&self, _elapsed: Duration) -> i32 {
+        0
+    }
+
+    /// Format a score in the units this policy uses.
+    fn format_score(&self, score: i32) -> String {
+        format!("{}pts", score)
+    }
+}
+
+/// 5 points for playing a card from the waste to the tableau or flipping a card, and 10 points
+/// for playing a card to the foundation. Moving a card back off the foundation takes back the 10
+/// points, but nothing else is ever taken away.
+pub struct Simple;
+
+impl ScoringPolicy for Simple {
+    fn score_event(&self, score: i32, event: ScoreEvent, _rules: &Rules) -> i32 {
+        use ScoreEvent::*;
+        score + match event {
+            WasteToTableau | FlipCard => 5,
+            WasteToFoundation | TableauToFoundation => 10,
+            FoundationToTableau => -10,
+            RecycleWaste | Undo => 0,
+        }
+    }
+}
+
+/// The standard scoring from the classic Windows version: points as in `Simple`, but moving a card
+/// off the foundation costs 15 points, recycling the waste costs 100 points when drawing one card
+/// at a time or 20 when drawing three, undoing a move costs 2 points, and the score never goes
+/// below zero. Winning a game that took longer than 30 seconds earns a bonus of 700,000 divided
+/// by the number of seconds taken.
+pub struct Standard;
+
+impl ScoringPolicy for Standard {
+    fn score_event(&self, score: i32, event: ScoreEvent, rules: &Rules) -> i32 {
+        use ScoreEvent::*;
+        let points = match event {
+            WasteToTableau | FlipCard => 5,
+            WasteToFoundation | TableauToFoundation => 10,
+            FoundationToTableau => -15,
+            RecycleWaste if rules.draw_count == 1 => -100,
+            RecycleWaste => -20,
+            Undo => -2,
+        };
+        (score + points).max(0)
+    }
+
+    fn time_bonus(&self, elapsed: Duration) -> i32 {
+        let secs = elapsed.as_secs();
+        if secs < 30 {
+            0
+        } else {
+            (700_000 / secs) as i32
+        }
+    }
+}
+
+/// Each game costs $52 to play, and pays $5 for each card played to the foundation (which is paid
+/// back if the card is moved off the foundation again). Winnings are kept in a bankroll that
+/// carries over from game to game.
+pub struct Vegas;
+
+impl ScoringPolicy for Vegas {
+    fn initial_score(&self) -> i32 {
+        -52
+    }
+
+    fn score_event(&self, score: i32, event: ScoreEvent, _rules: &Rules) -> i32 {
+        use ScoreEvent::*;
+        score + match event {
+            WasteToFoundation | TableauToFoundation => 5,
+            FoundationToTableau => -5,
+            WasteToTableau | FlipCard | RecycleWaste | Undo => 0,
+        }
+    }
+
+    fn format_score(&self, score: i32) -> String {
+        if score < 0 {
+            format!("-${}", -score)
+        } else {
+            format!("${}", score)
+        }
+    }
+}
+
+/// Names the scoring policy chosen for a game, so that it can be written in logs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScoringMode {
+    Simple,
+    Standard,
+    Vegas,
+}
+
+impl ScoringMode {
+    pub fn policy(self) -> &'static dyn ScoringPolicy {
+        match self {
+            ScoringMode::Simple => &Simple,
+            ScoringMode::Standard => &Standard,
+            ScoringMode::Vegas => &Vegas,
+        }
+    }
+
+    /// Format a score or amount of money in the units this scoring mode uses.
+    pub fn format_score(self, score: i32) -> String {
+        self.policy().format_score(score)
+    }
+}
+
+impl Display for ScoringMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ScoringMode::Simple => "simple",
+            ScoringMode::Standard => "standard",
+            ScoringMode::Vegas => "vegas",
+        })
+    }
+}
+
+impl std::str::FromStr for ScoringMode {
+    type Err = RuleError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "simple" => Ok(ScoringMode::Simple),
+            "standard" => Ok(ScoringMode::Standard),
+            "vegas" => Ok(ScoringMode::Vegas),
+            _ => Err(RuleError::Scoring),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_standard() {
+        let draw_one = Rules { draw_count: 1, ..Rules::default() };
+        let draw_three = Rules { draw_count: 3, ..Rules::default() };
+        let policy = ScoringMode::Standard.policy();
+
+        assert_eq!(0, policy.initial_score());
+        assert_eq!(15, policy.score_event(5, ScoreEvent::TableauToFoundation, &draw_one));
+        assert_eq!(80, policy.score_event(100, ScoreEvent::RecycleWaste, &draw_three));
+        assert_eq!(0, policy.score_event(80, ScoreEvent::RecycleWaste, &draw_one));
+        assert_eq!(8, policy.score_event(10, ScoreEvent::Undo, &draw_one));
+
+        assert_eq!(0, policy.time_bonus(Duration::from_secs(29)));
+        assert_eq!(7000, policy.time_bonus(Duration::from_secs(100)));
+    }
+
+    #[test]
+    fn test_vegas() {
+        let rules = Rules { scoring: ScoringMode::Vegas, ..Rules::default() };
+        let policy = rules.scoring.policy();
+
+        assert_eq!(-52, policy.initial_score());
+        assert_eq!(-47, policy.score_event(-52, ScoreEvent::WasteToFoundation, &rules));
+        assert_eq!(-47, policy.score_event(-47, ScoreEvent::FlipCard, &rules));
+        assert_eq!("-$47", policy.format_score(-47));
+        assert_eq!("$13", policy.format_score(13));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::action::{Action, Destination, Source};
+use crate::game_state::{Facing, GameState, Rank, Suit, UndoRecord};
+use crate::hint::rank_move;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+pub enum Verdict {
+    /// The game can be won, by making these moves.
+    Winnable(Vec<Action>),
+    /// No sequence of moves wins the game.
+    Unwinnable,
+    /// The search gave up before finding out.
+    Unknown,
+}
+
+/// Search for a way to win the game from the given position, giving up after looking at
+/// `node_limit` positions.
+///
+/// This is a depth-first search, trying the most promising moves first. Positions which have
+/// been seen before are skipped, and moves which are always safe to make (flipping cards, and
+/// playing cards to the foundation which nothing else could go on) are made right away without
+/// considering alternatives.
+pub fn solve(state: &GameState, node_limit: usize) -> Verdict {
+    let mut solver = Solver {
+        seen: HashSet::new(),
+        path: vec![],
+        nodes: 0,
+        node_limit,
+    };
+    let mut state = state.clone();
+    solver.play_forced(&mut state);
+    match solver.search(&mut state) {
+        Some(true) => Verdict::Winnable(solver.path),
+        Some(false) => Verdict::Unwinnable,
+        None => Verdict::Unknown,
+    }
+}
+
+struct Solver {
+    /// The keys of positions already searched.
+    seen: HashSet<Vec<u8>>,
+    path: Vec<Action>,
+    nodes: usize,
+    node_limit: usize,
+}
+
+impl Solver {
+    /// Returns whether the game can be won from this position, leaving the winning moves in
+    /// `path`, or None if the node limit was reached. Moves tried are undone again, unless they
+    /// win.
+    fn search(&mut self, state: &mut GameState) -> Option<bool> {
+        if state.is_won() {
+            return Some(true);
+        }
+        if !self.seen.insert(state.position_key()) {
+            return Some(false);
+        }
+        self.nodes += 1;
+        if self.nodes > self.node_limit {
+            return None;
+        }
+
+        for action in candidate_moves(state) {
+            let depth = self.path.len();
+            let mut undo = vec![state.apply_action(&action).expect("solver tried an illegal move")];
+            self.path.push(action);
+            undo.extend(self.play_forced(state));
+            if self.search(state)? {
+                return Some(true);
+            }
+            for record in undo.iter().rev() {
+                state.undo_action(record);
+            }
+            self.path.truncate(depth);
+        }
+        Some(false)
+    }
+
+    /// Make all the moves which can't possibly hurt, returning the records to undo them.
+    fn play_forced(&mut self, state: &mut GameState) -> Vec<UndoRecord> {
+        let mut undo = vec![];
+        while let Some(action) = forced_move(state) {
+            undo.push(state.apply_action(&action).expect("solver tried an illegal forced move"));
+            self.path.push(action);
+        }
+        undo
+    }
+}
+
+/// Legal moves worth trying, best first.
+fn candidate_moves(state: &GameState) -> Vec<Action> {
+    let mut moves = state.legal_moves()
+        .into_iter()
+        .filter(|action| !is_redundant(state, action))
+        .filter_map(|action| rank_move(state, &action).map(|rank| (rank, action)))
+        .collect::<Vec<_>>();
+    moves.sort_by_key(|(rank, _)| Reverse(*rank));
+    moves.into_iter().map(|(_, action)| action).collect()
+}
+
+/// Whether a move is the same as another legal move, apart from which empty foundation pile or
+/// tableau column it goes to.
+fn is_redundant(state: &GameState, action: &Action) -> bool {
+    match action {
+        Action::Move(_, Destination::Foundation(idx)) => {
+            state.foundation(*idx).is_none()
+                && (0 .. *idx).any(|i| state.foundation(i).is_none())
+        }
+        Action::Move(_, Destination::Tableau(column)) => {
+            state.tableau(*column).is_empty()
+                && (0 .. *column).any(|i| state.tableau(i).is_empty())
+        }
+        _ => false,
+    }
+}
+
+/// A move that can always be made without losing anything: flipping over a face-down card, or
+/// playing a card from the tableau to the foundation when no card that could go on it in the
+/// tableau is still needed there.
+fn forced_move(state: &GameState) -> Option<Action> {
+    for column in 0 .. 7 {
+        let cards = state.tableau(column);
+        let row = match cards.len() {
+            0 => continue,
+            len => len - 1,
+        };
+        let src = Source::Tableau { column, row };
+        let (card, facing) = cards[row];
+        if let Facing::Down = facing {
+            return Some(Action::QuickMove(src));
+        }
+
+        let rank = card.rank.value();
+        let safe = rank <= Rank::N2.value()
+            || Suit::all().iter()
+                .filter(|suit| suit.color() != card.suit.color())
+                .all(|&suit| foundation_rank(state, suit) + 1 >= rank);
+        if !safe {
+            continue;
+        }
+        for idx in 0 .. 4 {
+            let fits = match state.foundation(idx) {
+                Some(top) => top.suit == card.suit && top.rank.value() + 1 == rank,
+                None => card.rank == Rank::Ace,
+            };
+            if fits {
+                return Some(Action::Move(src, Destination::Foundation(idx)));
+            }
+        }
+    }
+    None
+}
+
+/// The rank of the highest card of the given suit on the foundation, or 0 if there is none.
+fn foundation_rank(state: &GameState, suit: Suit) -> u8 {
+    (0 .. 4)
+        .filter_map(|idx| state.foundation(idx))
+        .find(|card| card.suit == suit)
+        .map_or(0, |card| card.rank.value())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_state::Card;
+    use crate::rules::Rules;
+
+    #[test]
+    fn test_solve_unshuffled() {
+        let mut deck = vec![];
+        for &rank in Rank::all() {
+            for &suit in Suit::all() {
+                deck.push(Card { suit, rank });
+            }
+        }
+        let rules = Rules { draw_count: 1, ..Rules::default() };
+        let mut state = GameState::new(0, rules, deck);
+
+        let moves = match solve(&state, 100_000) {
+            Verdict::Winnable(moves) => moves,
+            Verdict::Unwinnable => panic!("unwinnable"),
+            Verdict::Unknown => panic!("unknown"),
+        };
+        for action in &moves {
+            state.apply_action(action).unwrap();
+        }
+        assert!(state.is_won());
+    }
+}
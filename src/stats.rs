@@ -0,0 +1,218 @@
+use crate::error::Error;
+use crate::rules::{self, Rules};
+use std::fmt::{self, Display};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::num::ParseIntError;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The outcome of one game, for keeping statistics.
+#[derive(Debug, Clone)]
+pub struct GameResult {
+    pub game_number: u64,
+    pub rules: Rules,
+    pub score: i32,
+    pub moves: usize,
+    pub duration: Duration,
+    pub won: bool,
+}
+
+impl Display for GameResult {
+    /// One line of the statistics file: the game number, draw count, pass limit, scoring mode,
+    /// score, moves, duration in seconds, and `won` or `lost`, separated by spaces.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {} {} {} {} {}",
+            self.game_number,
+            self.rules.draw_count,
+            rules::pass_limit_str(self.rules.pass_limit),
+            self.rules.scoring,
+            self.score,
+            self.moves,
+            self.duration.as_secs(),
+            if self.won { "won" } else { "lost" })
+    }
+}
+
+impl std::str::FromStr for GameResult {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 8 {
+            return Err(Error::FieldCount { expected: 8, found: fields.len() });
+        }
+        let bad = |name, e: ParseIntError| Error::Field { name, error: Box::new(e.into()) };
+        let rules = Rules {
+            draw_count: Rules::parse_draw_count(fields[1])?,
+            pass_limit: Rules::parse_pass_limit(fields[2])?,
+            scoring: fields[3].parse()?,
+        };
+        Ok(GameResult {
+            game_number: fields[0].parse().map_err(|e| bad("game number", e))?,
+            rules,
+            score: fields[4].parse().map_err(|e| bad("score", e))?,
+            moves: fields[5].parse().map_err(|e| bad("move count", e))?,
+            duration: Duration::from_secs(fields[6].parse().map_err(|e| bad("duration", e))?),
+            won: match fields[7] {
+                "won" => true,
+                "lost" => false,
+                other => return Err(Error::BadResult(other.to_owned())),
+            },
+        })
+    }
+}
+
+fn path(dir: &Path) -> PathBuf {
+    dir.join("stats")
+}
+
+/// Load the results of every game recorded so far in a data directory, oldest first.
+pub fn load(dir: &Path) -> Result<Vec<GameResult>, Error> {
+    let file = match File::open(path(dir)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    read(BufReader::new(file))
+}
+
+fn read(r: impl BufRead) -> Result<Vec<GameResult>, Error> {
+    let mut results = vec![];
+    for (i, line) in r.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        results.push(line.parse().map_err(|e: Error| e.at_line("stats", i + 1))?);
+    }
+    Ok(results)
+}
+
+/// Add the result of a game to the statistics file in a data directory.
+pub fn record(dir: &Path, result: &GameResult) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path(dir))?;
+    writeln!(file, "{}", result)
+}
+
+/// Statistics summarizing a player's games.
+#[derive(Debug)]
+pub struct Summary<'a> {
+    pub played: usize,
+    pub won: usize,
+    /// The best game under each scoring mode that has been played, in the order they were first
+    /// played, since scores from different modes can't be compared.
+    pub best_scores: Vec<&'a GameResult>,
+    pub fastest_win: Option<&'a GameResult>,
+    pub current_streak: usize,
+    pub longest_streak: usize,
+}
+
+impl<'a> Summary<'a> {
+    /// Summarize results given oldest first.
+    pub fn new(results: &'a [GameResult]) -> Self {
+        let mut summary = Summary {
+            played: results.len(),
+            won: 0,
+            best_scores: vec![],
+            fastest_win: None,
+            current_streak: 0,
+            longest_streak: 0,
+        };
+        for result in results {
+            let best = summary.best_scores.iter_mut()
+                .find(|best| best.rules.scoring == result.rules.scoring);
+            match best {
+                Some(best) if result.score > best.score => *best = result,
+                Some(_) => (),
+                None => summary.best_scores.push(result),
+            }
+            if result.won {
+                summary.won += 1;
+                summary.current_streak += 1;
+                summary.longest_streak = summary.longest_streak.max(summary.current_streak);
+                if summary.fastest_win.map_or(true, |fastest| result.duration < fastest.duration) {
+                    summary.fastest_win = Some(result);
+                }
+            } else {
+                summary.current_streak = 0;
+            }
+        }
+        summary
+    }
+
+    /// Percentage of games played that were won.
+    pub fn win_rate(&self) -> f64 {
+        if self.played == 0 {
+            0.
+        } else {
+            self.won as f64 * 100. / self.played as f64
+        }
+    }
+}
+
+impl<'a> Display for Summary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "games played:   {}", self.played)?;
+        writeln!(f, "games won:      {} ({:.0}%)", self.won, self.win_rate())?;
+        for best in &self.best_scores {
+            writeln!(f, "{:<16}{} (game #{})", format!("best {}:", best.rules.scoring),
+                best.rules.scoring.format_score(best.score), best.game_number)?;
+        }
+        if let Some(fastest) = self.fastest_win {
+            let secs = fastest.duration.as_secs();
+            writeln!(f, "fastest win:    {}:{:02} (game #{})",
+                secs / 60, secs % 60, fastest.game_number)?;
+        }
+        writeln!(f, "current streak: {}", self.current_streak)?;
+        write!(f, "longest streak: {}", self.longest_streak)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scoring::ScoringMode;
+
+    fn result(game_number: u64, score: i32, secs: u64, won: bool) -> GameResult {
+        GameResult {
+            game_number,
+            rules: Rules::default(),
+            score,
+            moves: 100,
+            duration: Duration::from_secs(secs),
+            won,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut saved = result(1234, -30, 95, false);
+        saved.rules = Rules { draw_count: 1, pass_limit: Some(3), scoring: ScoringMode::Vegas };
+        let loaded = read(format!("{}\n", saved).as_bytes()).unwrap();
+        assert_eq!(1, loaded.len());
+        assert_eq!(saved.to_string(), loaded[0].to_string());
+        assert_eq!(saved.rules, loaded[0].rules);
+    }
+
+    #[test]
+    fn test_summary() {
+        let vegas = Rules { scoring: ScoringMode::Vegas, ..Rules::default() };
+        let results = [
+            result(1, 100, 300, true),
+            result(2, 120, 200, true),
+            result(3, 40, 50, false),
+            result(4, 90, 250, true),
+            GameResult { rules: vegas, ..result(5, 20, 400, false) },
+            GameResult { rules: vegas, ..result(6, -30, 400, false) },
+        ];
+        let summary = Summary::new(&results);
+        assert_eq!(6, summary.played);
+        assert_eq!(3, summary.won);
+        assert_eq!(50., summary.win_rate());
+        let best = summary.best_scores.iter().map(|r| r.game_number).collect::<Vec<_>>();
+        assert_eq!(vec![2, 5], best);
+        assert_eq!(2, summary.fastest_win.unwrap().game_number);
+        assert_eq!(0, summary.current_streak);
+        assert_eq!(2, summary.longest_streak);
+    }
+}
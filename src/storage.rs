@@ -0,0 +1,47 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The per-user directory where klon keeps its files, creating it if necessary.
+pub fn data_dir() -> io::Result<PathBuf> {
+    let dir = platform_data_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory found"))?
+        .join("klon");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+#[cfg(unix)]
+fn platform_data_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    env::var_os("HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(|home| PathBuf::from(home).join(".local").join("share"))
+}
+
+#[cfg(windows)]
+fn platform_data_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Load the Vegas scoring bankroll, in dollars. A missing file means a fresh bankroll of zero.
+pub fn load_bankroll(dir: &Path) -> io::Result<i32> {
+    let path = dir.join("bankroll");
+    match fs::read_to_string(path) {
+        Ok(contents) => contents.trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn save_bankroll(dir: &Path, bankroll: i32) -> io::Result<()> {
+    let path = dir.join("bankroll");
+    fs::write(path, format!("{}\n", bankroll))
+}
@@ -1,4 +1,7 @@
-use crate::game_state::{Card, Color as CardColor, Facing, GameState};
+use klon::action::{Action, Destination, Source};
+use klon::frontend::{EndChoice, Frontend, HELP};
+use klon::game_state::{Card, Color as CardColor, Facing, GameState, GameStatus};
+use klon::viewer::Viewer;
 use pancurses::*;
 
 pub struct CursesUI {
@@ -8,12 +11,21 @@ pub struct CursesUI {
     tableau: [Window; 7],
     foundation: [Window; 4],
     text_window: Window,
+    bankroll: Option<i32>,
+    hint: Option<Action>,
 }
 
 const WHITE_ON_BLACK: i16 = 0;
 const RED_ON_BLACK: i16 = 1;
 const BLACK_ON_BLACK: i16 = 2;
 
+const PRESS_ANY_KEY: &str = "Press any key to return to the game.";
+
+/// How long each move is shown for when playing a replay.
+const PLAY_DELAY_MS: i32 = 700;
+
+const VIEWER_KEYS: &str = "spc play/pause  <- -> step  g go to  q quit";
+
 #[derive(Debug, Copy, Clone)]
 enum Color {
     Gray,
@@ -24,6 +36,7 @@ enum Color {
 trait WindowExt {
     fn color(&self, color: Color);
     fn underline(&self, enabled: bool);
+    fn highlight(&self, enabled: bool);
 }
 
 impl WindowExt for Window {
@@ -51,6 +64,14 @@ impl WindowExt for Window {
     // Underline doesn't do what we want on pdcurses win32.
     #[cfg(windows)]
     fn underline(&self, _enabled: bool) {}
+
+    fn highlight(&self, enabled: bool) {
+        if enabled {
+            self.attron(A_REVERSE);
+        } else {
+            self.attroff(A_REVERSE);
+        }
+    }
 }
 
 // Used to make an array of a type that needs explicit initialization.
@@ -70,7 +91,7 @@ macro_rules! init_array {
             }
 
             // This is safe because the array is fully initialized now.
-            unsafe { mem::transmute::<_, [$ty; $n]>(uninit) }
+            unsafe { mem::transmute::<[MaybeUninit<$ty>; $n], [$ty; $n]>(uninit) }
         }
     }
 }
@@ -112,7 +133,8 @@ impl CursesUI {
         // 123456 12345678901
         // __DD__ _W1 _W2 _W3
         // draw 3 10X 10Y 10Z
-        let draw_button = newwin(2,  6, 1, 0);
+        // pass 1
+        let draw_button = newwin(3,  8, 1, 0);
         let waste       = newwin(2, 11, 1, 8);
 
         // Stacks of cards:
@@ -136,6 +158,8 @@ impl CursesUI {
             tableau,
             foundation,
             text_window,
+            bankroll: None,
+            hint: None,
         }
     }
 
@@ -152,10 +176,104 @@ impl CursesUI {
         win.addstr(&card_str);
     }
 
-    pub fn render(&self, game: &GameState) {
-        self.main_window.mvaddstr(0, 0, &format!("game #{}", game.game_number()));
+    /// Wait for a single key press, for up to `timeout_ms` milliseconds if given. Returns None if
+    /// the time runs out first, or there is no more input.
+    fn get_key(&self, timeout_ms: Option<i32>) -> Option<Input> {
+        cbreak();
+        noecho();
+        self.text_window.keypad(true);
+        self.text_window.timeout(timeout_ms.unwrap_or(-1));
+        let input = self.text_window.getch();
+        self.text_window.timeout(-1);
+        self.text_window.keypad(false);
+        echo();
+        nocbreak();
+        input
+    }
+
+    /// Replace both lines of the text area, for showing status rather than asking for a move.
+    fn show_status(&self, line1: &str, line2: &str) {
+        for (y, line) in [line1, line2].iter().enumerate() {
+            self.text_window.mv(y as i32, 0);
+            self.text_window.clrtoeol();
+            self.text_window.addstr(line);
+        }
+        self.text_window.refresh();
+    }
+
+    fn popup(&self, win: Window) {
+        win.draw_box('|', '-');
+
+        cbreak();
+        win.getch();
+        nocbreak();
+        win.delwin();
+
+        // Clear and redraw the screen because we drew in between windows.
+        self.main_window.erase();
+        self.main_window.refresh();
+    }
+
+    /// Show a logged game in the viewer, letting the player step through it, until they quit.
+    fn run_viewer(&mut self, viewer: &mut Viewer) {
+        let mut playing = false;
+        let mut message = None;
+        loop {
+            self.render(viewer.state());
+            self.show_status(&viewer.status(playing), message.take().unwrap_or(VIEWER_KEYS));
+            let timeout = if playing { Some(PLAY_DELAY_MS) } else { None };
+            match self.get_key(timeout) {
+                None if playing => playing = viewer.step_forward(),
+                None => return,
+                Some(Input::Character(' ')) => {
+                    if !playing && viewer.position() == viewer.move_count() {
+                        // Play again from the start.
+                        viewer.jump(0);
+                    }
+                    playing = !playing;
+                }
+                Some(Input::KeyRight) | Some(Input::Character('.')) => {
+                    playing = false;
+                    viewer.step_forward();
+                }
+                Some(Input::KeyLeft) | Some(Input::Character(',')) => {
+                    playing = false;
+                    viewer.step_back();
+                }
+                Some(Input::KeyHome) => viewer.jump(0),
+                Some(Input::KeyEnd) => viewer.jump(viewer.move_count()),
+                Some(Input::Character('g')) | Some(Input::Character('G')) => {
+                    playing = false;
+                    self.show_status("", "");
+                    let input = self.prompt("go to move: ");
+                    match input.as_deref().map(str::trim).map(str::parse) {
+                        Some(Ok(n)) => viewer.jump(n),
+                        Some(Err(_)) => message = Some("that's not a move number"),
+                        None => return,
+                    }
+                }
+                Some(Input::Character('q')) | Some(Input::Character('Q')) => return,
+                Some(_) => (),
+            }
+        }
+    }
+}
+
+impl Frontend for CursesUI {
+    fn render(&mut self, game: &GameState) {
+        let (hint_src, hint_dest) = match &self.hint {
+            Some(Action::Move(src, dest)) => (Some(src), Some(dest)),
+            Some(Action::QuickMove(src)) => (Some(src), None),
+            _ => (None, None),
+        };
 
-        let points = format!("{}pts", game.score());
+        self.main_window.mvaddstr(0, 0, format!("game #{}", game.game_number()));
+
+        let scoring = game.rules().scoring;
+        let mut points = scoring.format_score(game.score());
+        if let Some(bankroll) = self.bankroll {
+            points += &format!(" bank {}", scoring.format_score(bankroll + game.score()));
+        }
         self.main_window.clrtoeol();
         self.main_window.mvaddstr(0, 47 - points.len() as i32, &points);
         self.main_window.refresh();
@@ -163,19 +281,32 @@ impl CursesUI {
         self.draw_button.mv(0, 0);
         self.draw_button.color(Color::Gray);
         self.draw_button.underline(true);
+        self.draw_button.highlight(matches!(self.hint, Some(Action::Draw)));
         self.draw_button.addstr("  DD  ");
+        self.draw_button.highlight(false);
         self.draw_button.underline(false);
         self.draw_button.color(Color::Normal);
-        let stock_size = game.stock_size().min(3);
+        let stock_size = game.stock_size().min(game.draw_count());
+        self.draw_button.mv(1, 0);
+        self.draw_button.clrtoeol();
         if stock_size == 0 {
             if game.waste().is_empty() {
                 self.draw_button.addstr(" empty");
+            } else if game.stock_exhausted() {
+                self.draw_button.addstr(" done");
             } else {
                 self.draw_button.addstr("recycle");
             }
         } else {
-            self.draw_button.addstr(&format!("draw {}", stock_size));
+            self.draw_button.addstr(format!("draw {}", stock_size));
         }
+        self.draw_button.mv(2, 0);
+        self.draw_button.clrtoeol();
+        self.draw_button.color(Color::Gray);
+        match game.rules().pass_limit {
+            Some(limit) => self.draw_button.addstr(format!("pass {}/{}", game.passes(), limit)),
+            None => self.draw_button.addstr(format!("pass {}", game.passes())),
+        };
         self.draw_button.refresh();
 
         let waste = game.waste();
@@ -188,7 +319,9 @@ impl CursesUI {
             for i in 0 .. waste.len() {
                 if i == waste.len() - 1 {
                     self.waste.underline(true);
+                    self.waste.highlight(hint_src == Some(&Source::Waste));
                     self.waste.addstr(" W ");
+                    self.waste.highlight(false);
                     self.waste.underline(false);
                     self.waste.mv(1, 0);
                 } else {
@@ -208,7 +341,10 @@ impl CursesUI {
             win.mv(0, 0);
             win.color(Color::Gray);
             win.underline(true);
-            win.addstr(&format!(" 0{} ", (b'A' + i as u8) as char));
+            win.highlight(hint_src == Some(&Source::Foundation(i))
+                || hint_dest == Some(&Destination::Foundation(i)));
+            win.addstr(format!(" 0{} ", (b'A' + i as u8) as char));
+            win.highlight(false);
             win.underline(false);
 
             match game.foundation(i) {
@@ -230,10 +366,16 @@ impl CursesUI {
             win.mv(0, 0);
             win.color(Color::Gray);
             win.underline(true);
-            win.addstr(&format!("     {}\n", i + 1));
+            win.highlight(hint_dest == Some(&Destination::Tableau(i)));
+            win.addstr(format!("     {}", i + 1));
+            win.highlight(false);
             win.underline(false);
+            win.addstr("\n");
             for (j, (card, facing)) in game.tableau(i).iter().enumerate() {
-                win.addstr(&format!("{}{} ", i + 1, (b'A' + j as u8) as char));
+                win.highlight(hint_src == Some(&Source::Tableau { column: i, row: j }));
+                win.addstr(format!("{}{}", i + 1, (b'A' + j as u8) as char));
+                win.highlight(false);
+                win.addstr(" ");
                 if matches!(facing, Facing::Down) {
                     win.addstr("---\n");
                 } else {
@@ -246,10 +388,13 @@ impl CursesUI {
         }
     }
 
-    pub fn get_input(&self) -> Option<String> {
+    fn get_input(&mut self) -> Option<String> {
+        self.prompt("your move: ")
+    }
+
+    fn prompt(&mut self, prompt: &str) -> Option<String> {
         let mut line = String::new();
 
-        let prompt = "your move: ";
         self.text_window.mv(0, 0);
         self.text_window.clrtoeol();
         self.text_window.refresh();
@@ -286,35 +431,105 @@ impl CursesUI {
         Some(line)
     }
 
-    pub fn write(&self, txt: &str) {
+    fn confirm(&mut self, question: &str) -> bool {
+        self.text_window.mv(0, 0);
+        self.text_window.clrtoeol();
+        self.text_window.addstr(question);
+        self.text_window.refresh();
+        curs_set(1);
+        // Read the answer as a single key, so there's no newline left over for the next input.
+        cbreak();
+        let answer = loop {
+            match self.text_window.getch() {
+                Some(Input::Character('y')) | Some(Input::Character('Y')) => break true,
+                Some(Input::Character('n')) | Some(Input::Character('N')) | None => break false,
+                Some(_) => (),
+            }
+        };
+        nocbreak();
+        curs_set(0);
+        self.text_window.mv(0, 0);
+        self.text_window.clrtoeol();
+        answer
+    }
+
+    fn write(&mut self, txt: &str) {
         self.text_window.mvaddstr(1, 0, txt);
     }
 
-    pub fn halp(&self) {
+    /// Show some lines of text in a box over the game, until a key is pressed.
+    fn show_text(&mut self, text: &str) {
+        let lines = text.lines().collect::<Vec<_>>();
+        let width = lines.iter()
+            .map(|line| line.chars().count())
+            .chain(std::iter::once(PRESS_ANY_KEY.len()))
+            .max()
+            .unwrap_or(0);
+        let win = newwin(lines.len() as i32 + 4, width as i32 + 4, 2, 4);
+        for (i, line) in lines.iter().chain(std::iter::once(&PRESS_ANY_KEY)).enumerate() {
+            win.mvaddstr(i as i32 + 1 + (i == lines.len()) as i32, 2, line);
+        }
+        self.popup(win);
+    }
+
+    fn halp(&mut self) {
         let win = newwin(13, 40, 2, 4);
-        // Note the space before the line-continuation backslash; it makes room for the border.
-        win.addstr("\n \
-                    Move cards by typing the position of\n \
-                    the card to be moved, followed by the\n \
-                    destination. The columns of cards are\n \
-                    numbered, and the rows are letters. To\n \
-                    place at the bottom of a column, just\n \
-                    specify the column number. Flip a\n \
-                    face-down card over by just typing its\n \
-                    position, without any destination. As\n \
-                    a shortcut, moves to the foundation\n \
-                    can omit the destination.\n \
-                    Press any key to return to the game.");
+        for (i, line) in HELP.lines().chain(std::iter::once(PRESS_ANY_KEY)).enumerate() {
+            win.mvaddstr(i as i32 + 1, 1, line);
+        }
+        self.popup(win);
+    }
+
+    fn pause(&mut self, ms: i32) {
+        napms(ms);
+    }
+
+    fn end_screen(&mut self, game: &GameState, status: GameStatus) -> EndChoice {
+        let headline = match status {
+            GameStatus::Won => "YOU'RE WINNER !", // lol
+            GameStatus::NoMovesLeft | GameStatus::InProgress => "No moves left.",
+        };
+        let score = game.rules().scoring.format_score(game.score());
+
+        let win = newwin(8, 40, 6, 4);
+        win.addstr(format!("\n {}\n Final score: {}\n\n \
+                            Press N for a new deal, R to replay\n \
+                            this deal, or Q to quit.",
+            headline, score));
         win.draw_box('|', '-');
 
         cbreak();
-        win.getch();
+        let choice = loop {
+            match win.getch() {
+                Some(Input::Character(c)) => match c.to_ascii_lowercase() {
+                    'n' => break EndChoice::NewDeal,
+                    'r' => break EndChoice::Replay,
+                    'q' => break EndChoice::Quit,
+                    _ => (),
+                },
+                Some(_) => (),
+                None => break EndChoice::Quit,
+            }
+        };
         nocbreak();
         win.delwin();
 
-        // Clear and redraw the screen because we drew in between windows.
         self.main_window.erase();
         self.main_window.refresh();
+        choice
+    }
+
+    fn set_hint(&mut self, hint: Option<Action>) {
+        self.hint = hint;
+    }
+
+    fn set_bankroll(&mut self, bankroll: Option<i32>) {
+        self.bankroll = bankroll;
+    }
+
+    fn view(&mut self, viewer: &mut Viewer) {
+        self.run_viewer(viewer);
+        self.show_status("", "");
     }
 }
 
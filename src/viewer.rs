@@ -0,0 +1,135 @@
+use crate::action::Action;
+use crate::deal;
+use crate::error::Error;
+use crate::game_state::{GameState, UndoRecord};
+use crate::movelog::Replay;
+
+/// Steps back and forth through the moves of a logged game, for watching how it was played.
+pub struct Viewer {
+    state: GameState,
+    moves: Vec<Action>,
+    /// Records for undoing the moves made so far.
+    undo: Vec<UndoRecord>,
+}
+
+impl Viewer {
+    /// Set up to view moves made from the start of a game, given along with the line of the log
+    /// each came from. Every move is checked up front, so the viewer never has to deal with one
+    /// that can't be made.
+    pub fn new(start: GameState, moves: Vec<(usize, Action)>) -> Result<Self, Error> {
+        let mut viewer = Self {
+            state: start,
+            moves: Vec::with_capacity(moves.len()),
+            undo: vec![],
+        };
+        for (line, action) in moves {
+            viewer.undo.push(viewer.state.apply_action(&action)
+                .map_err(|e| Error::from(e).at_line("log", line))?);
+            viewer.moves.push(action);
+        }
+        viewer.jump(0);
+        Ok(viewer)
+    }
+
+    /// Read a log file into a viewer, returning it along with any warnings about the log.
+    pub fn open(path: &str) -> Result<(Self, Vec<String>), Error> {
+        let (replay, header, warnings) = Replay::open(path)?;
+        let start = deal(replay.game_number, header.rules);
+        Ok((Self::new(start, replay.read_moves()?)?, warnings))
+    }
+
+    /// The position being shown.
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    /// How many moves there are in the whole game.
+    pub fn move_count(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// How many moves have been made to reach the position being shown.
+    pub fn position(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Make the next move, returning false if there are no more.
+    pub fn step_forward(&mut self) -> bool {
+        match self.moves.get(self.position()) {
+            Some(action) => {
+                let record = self.state.apply_action(action).expect("move was checked");
+                self.undo.push(record);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Take back the last move, returning false if at the start of the game.
+    pub fn step_back(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(record) => {
+                self.state.undo_action(&record);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Go to the position after a given number of moves, or the end if there aren't that many.
+    pub fn jump(&mut self, position: usize) {
+        while self.position() > position && self.step_back() {}
+        while self.position() < position && self.step_forward() {}
+    }
+
+    /// A line describing the position being shown, and whether the moves are being played.
+    pub fn status(&self, playing: bool) -> String {
+        let text = match self.position() {
+            0 => format!("game #{}, {} moves", self.state.game_number(), self.moves.len()),
+            n => format!("move {} of {}: {}", n, self.moves.len(), self.moves[n - 1]),
+        };
+        if playing {
+            text + " (playing)"
+        } else {
+            text
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rules::Rules;
+
+    #[test]
+    fn test_viewer() {
+        let start = deal(5, Rules::default());
+        let mut state = start.clone();
+        let mut moves = vec![];
+        for line in 1 ..= 10 {
+            let action = state.legal_moves().remove(0);
+            state.apply_action(&action).unwrap();
+            moves.push((line, action));
+        }
+
+        let mut viewer = Viewer::new(start.clone(), moves.clone()).unwrap();
+        assert_eq!(0, viewer.position());
+        assert_eq!(start, viewer.state);
+        assert!(!viewer.step_back());
+        viewer.jump(100);
+        assert_eq!(10, viewer.position());
+        assert_eq!(state, viewer.state);
+        assert!(!viewer.step_forward());
+        viewer.jump(3);
+        assert!(viewer.step_back());
+        assert_eq!(2, viewer.position());
+        viewer.jump(0);
+        assert_eq!(start, viewer.state);
+
+        // A move that can't be made is reported with its line.
+        moves.push((12, Action::QuickMove(crate::action::Source::Foundation(0))));
+        let e = Viewer::new(start, moves).err().unwrap();
+        assert!(matches!(e, Error::Line { file: "log", line: 12, .. }), "{:?}", e);
+        assert!(e.to_string().starts_with("log line 12: "), "{}", e);
+    }
+}
@@ -0,0 +1,173 @@
+use klon::frontend::Scripted;
+use klon::game::Game;
+use klon::save::{Command, SavedGame};
+use klon::solver::{self, Verdict};
+use klon::stats;
+use klon::{deal, Action, GameStatus, Rules, ScoringMode};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A data directory for one test, so that the files games write stay out of the player's data
+/// directory and away from other tests. It's removed again when the test is done.
+struct DataDir(PathBuf);
+
+impl DataDir {
+    fn new(test: &str) -> Self {
+        let dir = env::temp_dir().join(format!("klon-test-{}-{}", std::process::id(), test));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        DataDir(dir)
+    }
+}
+
+impl Drop for DataDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+/// Play a game from start to finish, with a script of input.
+fn play(dir: &DataDir, game_number: u64, rules: Rules, script: &[&str]) -> Game<Scripted> {
+    let mut game = Game::new(game_number, rules, Scripted::new(script));
+    game.set_data_dir(dir.0.clone());
+    game.main_loop();
+    game
+}
+
+#[test]
+fn test_moves_and_errors() {
+    let mut state = deal(1, Rules::default());
+    let action = state.legal_moves().remove(0);
+    let text = action.to_string();
+    let dir = DataDir::new("moves_and_errors");
+    let game = play(&dir, 1, Rules::default(), &["9Z", "0A", "help", &text, "quit"]);
+
+    let ui = game.frontend();
+    assert_eq!(vec!["unrecognized input. try 'help' or 'quit'", "foundation is empty"],
+        ui.messages);
+    assert_eq!(vec!["help"], ui.texts);
+    state.apply_action(&action).unwrap();
+    assert_eq!(Some(&state), ui.screen.as_ref());
+
+    // Quitting part way through saves the game instead of counting it.
+    let (results, _, suspended) = game.end();
+    assert!(results.is_empty());
+    assert_eq!(Some(1), suspended);
+    assert!(dir.0.join("saved_game").exists());
+}
+
+#[test]
+fn test_undo_redo() {
+    let dir = DataDir::new("undo_redo");
+    let game = play(&dir, 2, Rules::default(), &["DD", "DD", "undo", "undo", "undo", "redo", "q"]);
+    let ui = game.frontend();
+    assert_eq!(vec!["no moves to undo"], ui.messages);
+    let mut state = deal(2, Rules::default());
+    state.apply_action(&Action::Draw).unwrap();
+    assert_eq!(state.waste(), ui.screen.as_ref().unwrap().waste());
+}
+
+#[test]
+fn test_undone_game_counts() {
+    // Moves taken back still count as playing the game, so starting a new one counts as a loss.
+    let dir = DataDir::new("undone_game_counts");
+    let game = play(&dir, 2, Rules::default(), &["DD", "undo", "new", "q"]);
+    let (results, _, _) = game.end();
+    assert_eq!(1, results[0].moves);
+    let recorded = stats::load(&dir.0).unwrap();
+    assert_eq!(1, recorded.len());
+    assert_eq!(2, recorded[0].game_number);
+    assert!(!recorded[0].won);
+}
+
+#[test]
+fn test_unplayed_deals() {
+    // Deals left without making a move aren't counted, and don't cost the Vegas buy-in.
+    let rules = Rules { scoring: ScoringMode::Vegas, ..Rules::default() };
+    let dir = DataDir::new("unplayed_deals");
+    let mut game = Game::new(2, rules, Scripted::new(&["new", "q"]));
+    game.set_data_dir(dir.0.clone());
+    game.set_bankroll(Some(100));
+    game.main_loop();
+    let (results, bankroll, suspended) = game.end();
+    assert!(results.is_empty());
+    assert_eq!(Some(100), bankroll);
+    assert_eq!(None, suspended);
+    assert!(stats::load(&dir.0).unwrap().is_empty());
+}
+
+/// The moves that win game 2, drawing one card at a time.
+fn solve_game_2(rules: Rules) -> Vec<Action> {
+    match solver::solve(&deal(2, rules), 100_000) {
+        Verdict::Winnable(moves) => moves,
+        _ => panic!("game 2 should be winnable"),
+    }
+}
+
+#[test]
+fn test_win() {
+    let rules = Rules { draw_count: 1, ..Rules::default() };
+    let moves = solve_game_2(rules);
+    let mut script = moves.iter().map(Action::to_string).collect::<Vec<_>>();
+    // The name for the high score table, and then quit at the end of game screen.
+    script.push("Tester".to_owned());
+    script.push("q".to_owned());
+    let script = script.iter().map(String::as_str).collect::<Vec<_>>();
+    let dir = DataDir::new("win");
+    let game = play(&dir, 2, rules, &script);
+
+    let ui = game.frontend();
+    assert_eq!(1, ui.endings.len());
+    assert_eq!(GameStatus::Won, ui.endings[0].1);
+    assert!(ui.texts[0].contains("Tester"), "{}", ui.texts[0]);
+    let (results, _, suspended) = game.end();
+    assert_eq!(1, results.len());
+    assert!(results[0].won);
+    assert_eq!(moves.len(), results[0].moves);
+    assert_eq!(None, suspended);
+}
+
+#[test]
+fn test_win_undo_redo() {
+    // Winning ends the game straight away, so start from a saved game where the winning move was
+    // made and then undone.
+    let rules = Rules { draw_count: 1, ..Rules::default() };
+    let mut journal = solve_game_2(rules).into_iter().map(Command::Play).collect::<Vec<_>>();
+    journal.push(Command::Undo);
+    let dir = DataDir::new("win_undo_redo");
+    let saved = SavedGame { game_number: 2, rules, elapsed: Duration::default(), journal };
+    saved.save(&dir.0).unwrap();
+
+    let mut game = Game::new(2, rules, Scripted::new(&["y", "redo", "Tester", "q"]));
+    game.set_data_dir(dir.0.clone());
+    game.offer_resume();
+    game.main_loop();
+
+    let ui = game.frontend();
+    assert_eq!(1, ui.endings.len());
+    assert_eq!(GameStatus::Won, ui.endings[0].1);
+}
+
+#[test]
+fn test_goto_score() {
+    let rules = Rules { draw_count: 1, scoring: ScoringMode::Standard, ..Rules::default() };
+    let moves = solve_game_2(rules);
+    let mut script = moves.iter().map(Action::to_string).collect::<Vec<_>>();
+    script.insert(20, "mark here".to_owned());
+    script.truncate(40);
+    script.push("goto here".to_owned());
+    script.push("q".to_owned());
+    let script = script.iter().map(String::as_str).collect::<Vec<_>>();
+    let dir = DataDir::new("goto_score");
+    let game = play(&dir, 2, rules, &script);
+
+    let mut state = deal(2, rules);
+    for action in &moves[.. 20] {
+        state.apply_action(action).unwrap();
+    }
+    let ui = game.frontend();
+    assert!(ui.messages.is_empty(), "{:?}", ui.messages);
+    assert_eq!(state.score(), ui.screen.as_ref().unwrap().score());
+}